@@ -27,20 +27,47 @@ mod base {
     #[ink(storage)]
     pub struct Base {
         owner: AccountId,
+        // account the owner offered the ownership to and until when it can accept it
+        pending_owner: Option<(AccountId, u64)>,
         name: String,
         logo: String,
         desc: String,
     }
 
+    #[ink(event)]
+    pub struct OwnershipTransferProposed {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        pending_owner: AccountId,
+        expire_time: u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferCancelled {
+        #[ink(topic)]
+        pending_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
     impl Base {
 
+        /// the instantiator is the owner
         #[ink(constructor)]
         pub fn new() -> Self {
             Self {
                 name: String::default(),
                 logo: String::default(),
                 desc: String::default(),
-                owner: Default::default(),
+                owner: Self::env().caller(),
+                pending_owner: None,
             }
         }
 
@@ -49,15 +76,15 @@ mod base {
             Self::new()
         }
 
+        /// only the owner can init the base
         #[ink(message)]
         pub fn init_base(&mut self, name: String, logo: String, desc: String) {
+            if self.env().caller() != self.owner {
+                return;
+            }
             self.set_name(name);
             self.set_logo(logo);
             self.set_desc(desc);
-            // self.set_owner(owner);
-
-            let caller = self.env().caller();
-            self.set_owner(caller);
         }
 
         #[ink(message)]
@@ -90,14 +117,73 @@ mod base {
             self.desc.clone()
         }
 
+        /// offer the ownership to an account, which has `lifetime` to accept it. A new offer
+        /// replaces the pending one
         #[ink(message)]
-        pub fn set_owner(&mut self, owner: AccountId) {
+        pub fn transfer_ownership(&mut self, new_owner: AccountId, lifetime: u64) -> bool {
 
             let caller = self.env().caller();
 
-            if self.owner == AccountId::default() || caller == self.owner {
-                self.owner = owner;
+            if caller != self.owner || lifetime == 0 {
+                return false;
             }
+
+            let expire_time = self.env().block_timestamp().saturating_add(lifetime);
+            self.pending_owner = Some((new_owner, expire_time));
+
+            self.env().emit_event(OwnershipTransferProposed {
+                owner: caller,
+                pending_owner: new_owner,
+                expire_time,
+            });
+            true
+        }
+
+        /// become the owner, the caller has to be the pending owner and the offer not expired
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> bool {
+
+            let caller = self.env().caller();
+
+            match self.pending_owner {
+                Some((pending_owner, expire_time)) if pending_owner == caller && self.env().block_timestamp() < expire_time => {}
+                _ => return false,
+            }
+
+            let previous_owner = self.owner;
+            self.owner = caller;
+            self.pending_owner = None;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner: caller,
+            });
+            true
+        }
+
+        /// withdraw the pending offer, the caller has to be the owner or the pending owner
+        #[ink(message)]
+        pub fn cancel_ownership_transfer(&mut self) -> bool {
+
+            let caller = self.env().caller();
+
+            let pending_owner = match self.pending_owner {
+                Some((pending_owner, _)) if caller == self.owner || caller == pending_owner => pending_owner,
+                _ => return false,
+            };
+
+            self.pending_owner = None;
+
+            self.env().emit_event(OwnershipTransferCancelled {
+                pending_owner,
+            });
+            true
+        }
+
+        /// get the pending owner and until when it can accept the ownership
+        #[ink(message)]
+        pub fn get_pending_owner(&self) -> Option<(AccountId, u64)> {
+            self.pending_owner
         }
 
         #[ink(message)]
@@ -165,9 +251,7 @@ mod base {
 
             let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
 
-            let mut base = Base::default();
-
-            base.set_owner(accounts.alice);
+            let base = Base::default();
 
             assert_eq!(base.get_owner(), accounts.alice);
         }
@@ -179,13 +263,34 @@ mod base {
 
             let mut base = Base::default();
 
-            base.set_owner(accounts.alice);
+            assert_eq!(base.transfer_ownership(accounts.bob, 10), true);
 
             assert_eq!(base.get_owner(), accounts.alice);
+            assert_eq!(base.get_pending_owner(), Some((accounts.bob, 10)));
+
+            // only the pending owner can accept
+            assert_eq!(base.accept_ownership(), false);
 
-            base.set_owner(accounts.bob);
+            set_next_caller(accounts.bob);
+
+            assert_eq!(base.accept_ownership(), true);
 
             assert_eq!(base.get_owner(), accounts.bob);
+            assert_eq!(base.get_pending_owner(), None);
+
+            // an offer without expiry can't overflow the time
+            assert_eq!(base.transfer_ownership(accounts.charlie, u64::MAX), true);
+            assert_eq!(base.get_pending_owner(), Some((accounts.charlie, u64::MAX)));
+        }
+
+        fn set_next_caller(caller: AccountId) {
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                AccountId::from([0x07; 32]),
+                1_000_000,
+                0,
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])),
+            );
         }
 
 
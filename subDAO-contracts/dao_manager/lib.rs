@@ -17,9 +17,10 @@ mod dao_manager {
     use erc20::Erc20;
     use org::OrgManager;
     use vault::VaultManager;
-    use vote_manager::VoteManager;
+    use vote_manager::{VoteManager, ActionRules};
     use github::Github;
     use template_manager::DAOTemplate;
+    use auth::Auth;
 
     /// DAO component instances
     #[derive(scale::Encode, scale::Decode, Clone, SpreadLayout, PackedLayout)]
@@ -34,6 +35,7 @@ mod dao_manager {
         vault: Option<VaultManager>,
         vote: Option<VoteManager>,
         github: Option<Github>,
+        auth: Option<Auth>,
     }
 
     /// DAO component instance addresses
@@ -57,6 +59,8 @@ mod dao_manager {
         vote_addr: Option<AccountId>,
         // github module contract's address
         github_addr: Option<AccountId>,
+        // auth module contract's address
+        auth_addr: Option<AccountId>,
     }
 
     /// Defines the storage of your contract.
@@ -88,6 +92,7 @@ mod dao_manager {
                     vault: None,
                     vote: None,
                     github: None,
+                    auth: None,
                 },
                 component_addrs: DAOComponentAddrs {
                     base_addr: None,
@@ -96,6 +101,7 @@ mod dao_manager {
                     vault_addr: None,
                     vote_addr: None,
                     github_addr: None,
+                    auth_addr: None,
                 },
             }
         }
@@ -115,9 +121,11 @@ mod dao_manager {
             let vault_code_hash = components_hash_map.get("VAULT");
             let vote_code_hash = components_hash_map.get("VOTE");
             let github_code_hash = components_hash_map.get("GITHUB");
+            let auth_code_hash = components_hash_map.get("AUTH");
             self._init_base(base_code_hash, base_name, base_logo, base_desc);
             self._init_erc20(erc20_code_hash, erc20_name, erc20_symbol, erc20_initial_supply, erc20_decimals);
             self._init_org(org_code_hash);
+            self._init_auth(auth_code_hash);
             self._init_vault(vault_code_hash);
             self._init_vote(vote_code_hash);
             self._init_github(github_code_hash);
@@ -177,6 +185,17 @@ mod dao_manager {
             org.remove_dao_moderator(member)
         }
 
+        /// offer the ownership of the base and the org to an account, eg. the vote module so
+        /// they are governed by votes, which has `lifetime` to accept it by `accept_ownership`
+        #[ink(message)]
+        pub fn transfer_component_ownership(&mut self, new_owner: AccountId, lifetime: u64) -> bool {
+            let controller = self.env().caller();
+            assert_eq!(controller == self.controller, true);
+            let base_offered = self.components.base.as_mut().map_or(true, |base| base.transfer_ownership(new_owner, lifetime));
+            let org_offered = self.components.org.as_mut().map_or(true, |org| org.transfer_ownership(new_owner, lifetime));
+            base_offered && org_offered
+        }
+
         /// init base
         fn _init_base(&mut self, base_code_hash: Option<&Hash>,
                       base_name: String, base_logo: String, base_desc: String) -> bool {
@@ -251,7 +270,9 @@ mod dao_manager {
             let total_balance = Self::env().balance();
             // instance org
             let org_addr = self.component_addrs.org_addr.unwrap();
-            let vault_instance_params = VaultManager::new(org_addr)
+            // the vault checks withdrawal permissions in the auth module
+            let auth_addr = self.component_addrs.auth_addr.expect("the `Vault` contract needs the `Auth` component");
+            let vault_instance_params = VaultManager::new(org_addr, auth_addr)
                 .endowment(total_balance / 4)
                 .code_hash(vault_code_hash)
                 .params();
@@ -259,6 +280,8 @@ mod dao_manager {
             let vault_addr = vault_init_result.expect("failed at instantiating the `Org` contract");
             let mut vault_instance: VaultManager = ink_env::call::FromAccountId::from_account_id(vault_addr);
             vault_instance.add_vault_token(self.component_addrs.erc20_addr.unwrap());
+            // the org sends rejected applicants' deposits and membership dues to the vault
+            self.components.org.as_mut().unwrap().set_vault_address(vault_addr);
             self.components.vault = Some(vault_instance);
             self.component_addrs.vault_addr = Some(vault_addr);
             true
@@ -272,7 +295,14 @@ mod dao_manager {
             let vote_code_hash = vote_code_hash.unwrap().clone();
             let total_balance = Self::env().balance();
             // instance org
-            let vote_instance_params = VoteManager::new()
+            let org_addr = self.component_addrs.org_addr.unwrap();
+            // votes with an action need 10% participation and a majority of the org members or the dao token
+            let action_rules = ActionRules {
+                min_participation_bps: 1000,
+                min_approval_bps: 5000,
+                token: self.component_addrs.erc20_addr,
+            };
+            let vote_instance_params = VoteManager::new(org_addr, None, action_rules)
                 .endowment(total_balance / 4)
                 .code_hash(vote_code_hash)
                 .params();
@@ -281,10 +311,34 @@ mod dao_manager {
             let vote_instance = ink_env::call::FromAccountId::from_account_id(vote_addr);
             self.components.vote = Some(vote_instance);
             self.component_addrs.vote_addr = Some(vote_addr);
+            // token weighted votes snapshot the dao token
+            if let Some(erc20) = self.components.erc20.as_mut() {
+                erc20.set_snapshotter(Some(vote_addr));
+            }
             true
         }
 
         /// init github
+        /// init auth, owned by the dao manager
+        fn _init_auth(&mut self, auth_code_hash: Option<&Hash>) -> bool {
+            if auth_code_hash.is_none() {
+                return true;
+            }
+            let auth_code_hash = auth_code_hash.unwrap().clone();
+            let total_balance = Self::env().balance();
+            // instance auth
+            let auth_instance_params = Auth::new(Self::env().account_id())
+                .endowment(total_balance / 4)
+                .code_hash(auth_code_hash)
+                .params();
+            let auth_init_result = ink_env::instantiate_contract(&auth_instance_params);
+            let auth_addr = auth_init_result.expect("failed at instantiating the `Auth` contract");
+            let auth_instance = ink_env::call::FromAccountId::from_account_id(auth_addr);
+            self.components.auth = Some(auth_instance);
+            self.component_addrs.auth_addr = Some(auth_addr);
+            true
+        }
+
         fn _init_github(&mut self, github_code_hash: Option<&Hash>) -> bool {
             if github_code_hash.is_none() {
                 return true;
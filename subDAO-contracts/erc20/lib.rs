@@ -7,9 +7,23 @@ pub use self::erc20::Erc20;
 #[ink::contract]
 mod erc20 {
     use alloc::string::String;
+    use alloc::vec::Vec;
     use ink_storage::{
         collections::HashMap as StorageHashMap,
     };
+    #[cfg(not(test))]
+    use ink_env::call::{
+        build_call,
+        utils::ReturnType,
+        ExecutionInput,
+    };
+
+    type SnapshotId = u32;
+
+    /// selector of `on_token_received(from: AccountId, value: u64, data: Vec<u8>) -> bool`,
+    /// the message `transfer_and_call` notifies the receiver with
+    #[cfg(not(test))]
+    const ON_TOKEN_RECEIVED_SELECTOR: [u8; 4] = [0x41, 0xeb, 0x77, 0x88];
 
     /// Indicates whether a transaction is already confirmed or needs further confirmations.
     #[ink(storage)]
@@ -21,6 +35,13 @@ mod erc20 {
         balances: StorageHashMap<AccountId, u64>,
         allowances: StorageHashMap<(AccountId, AccountId), u64>,
         owner: AccountId,
+        // may take snapshots besides the owner, eg. the vote module
+        snapshotter: Option<AccountId>,
+        current_snapshot_id: SnapshotId,
+        // balance of an account as it was at the snapshot, recorded lazily
+        // before the first change after that snapshot
+        account_snapshots: StorageHashMap<AccountId, Vec<(SnapshotId, u64)>>,
+        total_supply_snapshots: Vec<(SnapshotId, u64)>,
     }
 
     #[ink(event)]
@@ -43,6 +64,12 @@ mod erc20 {
         value: u64,
     }
 
+    #[ink(event)]
+    pub struct Snapshot {
+        #[ink(topic)]
+        id: SnapshotId,
+    }
+
     impl Erc20 {
         #[ink(constructor)]
         pub fn new(name: String, symbol: String, initial_supply: u64, decimals: u8, controller: AccountId) -> Self {
@@ -56,6 +83,10 @@ mod erc20 {
                 balances,
                 allowances: StorageHashMap::new(),
                 owner: controller,
+                snapshotter: None,
+                current_snapshot_id: 0,
+                account_snapshots: StorageHashMap::new(),
+                total_supply_snapshots: Vec::new(),
             };
             Self::env().emit_event(Transfer {
                 from: None,
@@ -95,6 +126,67 @@ mod erc20 {
             self.balance_of_or_zero(&owner)
         }
 
+        /// take a snapshot of all balances and the total supply, return the snapshot id,
+        /// only by the owner or the snapshotter
+        #[ink(message)]
+        pub fn snapshot(&mut self) -> Option<SnapshotId> {
+            let caller = self.env().caller();
+            if caller != self.owner && self.snapshotter != Some(caller) {
+                return None
+            }
+            self.current_snapshot_id += 1;
+            let id = self.current_snapshot_id;
+            self.env().emit_event(Snapshot {
+                id,
+            });
+            Some(id)
+        }
+
+        /// set the account which may take snapshots besides the owner, only by the owner
+        #[ink(message)]
+        pub fn set_snapshotter(&mut self, snapshotter: Option<AccountId>) -> bool {
+            if self.env().caller() != self.owner {
+                return false
+            }
+            self.snapshotter = snapshotter;
+            true
+        }
+
+        #[ink(message)]
+        pub fn snapshotter(&self) -> Option<AccountId> {
+            self.snapshotter
+        }
+
+        #[ink(message)]
+        pub fn current_snapshot_id(&self) -> SnapshotId {
+            self.current_snapshot_id
+        }
+
+        /// none if there is no snapshot with this id
+        #[ink(message)]
+        pub fn balance_of_at(&self, owner: AccountId, snapshot_id: SnapshotId) -> Option<u64> {
+            if snapshot_id == 0 || snapshot_id > self.current_snapshot_id {
+                return None
+            }
+            let snapshots = self.account_snapshots.get(&owner);
+            match Self::value_at(snapshots.map(|v| v.as_slice()), snapshot_id) {
+                Some(value) => Some(value),
+                None => Some(self.balance_of_or_zero(&owner)),
+            }
+        }
+
+        /// none if there is no snapshot with this id
+        #[ink(message)]
+        pub fn total_supply_at(&self, snapshot_id: SnapshotId) -> Option<u64> {
+            if snapshot_id == 0 || snapshot_id > self.current_snapshot_id {
+                return None
+            }
+            match Self::value_at(Some(self.total_supply_snapshots.as_slice()), snapshot_id) {
+                Some(value) => Some(value),
+                None => Some(self.total_supply),
+            }
+        }
+
         #[ink(message)]
         pub fn allowance(&self, owner: AccountId, spender: AccountId) -> u64 {
             self.allowance_of_or_zero(&owner, &spender)
@@ -106,6 +198,18 @@ mod erc20 {
             self.transfer_from_to(from, to, value)
         }
 
+        /// transfer to a contract and call its `on_token_received` with the caller, the value and
+        /// the data, panics so the whole call is reverted if the call fails or returns false
+        #[ink(message, selector = "0xACD10E50")]
+        pub fn transfer_and_call(&mut self, to: AccountId, value: u64, data: Vec<u8>) -> bool {
+            let from = self.env().caller();
+            if !self.transfer_from_to(from, to, value) {
+                return false
+            }
+            assert!(self.notify_receiver(to, from, value, data), "the receiver didn't accept the transfer");
+            true
+        }
+
         #[ink(message)]
         pub fn approve(&mut self, spender: AccountId, value: u64) -> bool {
             let owner = self.env().caller();
@@ -166,6 +270,8 @@ mod erc20 {
             if from_balance < value {
                 return false
             }
+            self.update_account_snapshot(from);
+            self.update_account_snapshot(to);
             self.balances.insert(from, from_balance - value);
             let to_balance = self.balance_of_or_zero(&to);
             self.balances.insert(to, to_balance + value);
@@ -177,6 +283,40 @@ mod erc20 {
             true
         }
 
+        /// the value recorded for the first snapshot at or after `snapshot_id`,
+        /// `None` means the value has not changed since then
+        fn value_at(snapshots: Option<&[(SnapshotId, u64)]>, snapshot_id: SnapshotId) -> Option<u64> {
+            let snapshots = snapshots?;
+            // recorded ids are ascending
+            let index = match snapshots.binary_search_by_key(&snapshot_id, |(id, _)| *id) {
+                Ok(index) => index,
+                Err(index) => index,
+            };
+            snapshots.get(index).map(|(_, value)| *value)
+        }
+
+        fn update_account_snapshot(&mut self, account: AccountId) {
+            let current_id = self.current_snapshot_id;
+            if current_id == 0 {
+                return
+            }
+            let balance = self.balance_of_or_zero(&account);
+            let snapshots = self.account_snapshots.entry(account).or_insert(Vec::new());
+            if snapshots.last().map_or(true, |(id, _)| *id < current_id) {
+                snapshots.push((current_id, balance));
+            }
+        }
+
+        fn update_total_supply_snapshot(&mut self) {
+            let current_id = self.current_snapshot_id;
+            if current_id == 0 {
+                return
+            }
+            if self.total_supply_snapshots.last().map_or(true, |(id, _)| *id < current_id) {
+                self.total_supply_snapshots.push((current_id, self.total_supply));
+            }
+        }
+
         fn balance_of_or_zero(&self, owner: &AccountId) -> u64 {
             *self.balances.get(owner).unwrap_or(&0)
         }
@@ -198,6 +338,8 @@ mod erc20 {
             assert_eq!(total_supply + amount >= total_supply, true);
             let to_balance = self.balance_of_or_zero(&to);
             assert_eq!(to_balance + amount >= to_balance, true);
+            self.update_account_snapshot(to);
+            self.update_total_supply_snapshot();
             self.total_supply += amount;
             self.balances.insert(to, to_balance + amount);
             self.env().emit_event(Transfer {
@@ -217,6 +359,8 @@ mod erc20 {
             assert_eq!(total_supply - amount <= total_supply, true);
             let from_balance = self.balance_of_or_zero(&from);
             assert_eq!(from_balance - amount <= from_balance, true);
+            self.update_account_snapshot(from);
+            self.update_total_supply_snapshot();
             self.total_supply -= amount;
             self.balances.insert(from, from_balance - amount);
             self.env().emit_event(Transfer {
@@ -226,6 +370,55 @@ mod erc20 {
             });
             true
         }
+
+        /// call `on_token_received` of the receiver, false if it fails or isn't a contract,
+        /// off-chain tests call the `mock` module instead
+        #[cfg(not(test))]
+        fn notify_receiver(&self, to: AccountId, from: AccountId, value: u64, data: Vec<u8>) -> bool {
+            build_call::<<Self as ::ink_lang::ContractEnv>::Env>()
+                .callee(to)
+                .exec_input(
+                    ExecutionInput::new(ON_TOKEN_RECEIVED_SELECTOR.into())
+                        .push_arg(from)
+                        .push_arg(value)
+                        .push_arg(data),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+        #[cfg(test)]
+        fn notify_receiver(&self, to: AccountId, from: AccountId, value: u64, data: Vec<u8>) -> bool {
+            mock::on_token_received(to, from, value, data)
+        }
+    }
+
+    /// receivers of `transfer_and_call` for the off-chain tests, accounts not set aren't contracts
+    #[cfg(test)]
+    mod mock {
+        use super::*;
+        use std::{
+            cell::RefCell,
+            collections::BTreeMap,
+        };
+
+        thread_local! {
+            static RECEIVERS: RefCell<BTreeMap<AccountId, bool>> = RefCell::new(BTreeMap::new());
+        }
+
+        pub fn reset() {
+            RECEIVERS.with(|receivers| receivers.borrow_mut().clear());
+        }
+
+        /// make the account a contract which accepts or rejects the transfers
+        pub fn set_receiver(account: AccountId, accepts: bool) {
+            RECEIVERS.with(|receivers| receivers.borrow_mut().insert(account, accepts));
+        }
+
+        pub fn on_token_received(to: AccountId, _from: AccountId, _value: u64, _data: Vec<u8>) -> bool {
+            RECEIVERS.with(|receivers| *receivers.borrow().get(&to).unwrap_or(&false))
+        }
     }
 
      #[cfg(test)]
@@ -287,5 +480,134 @@ mod erc20 {
             ink_env::debug_println(&format!("alice balance  is {}", token.balance_of(accounts.alice)));
             assert_eq!(token.balance_of(accounts.alice), token.total_supply() - amount);
         }
+
+        #[ink::test]
+        fn snapshot_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+
+            let mut token = Erc20::new(
+                "SubDAO Token".to_string()
+                , "SDT".to_string()
+                , 1000
+                , 4
+                , accounts.alice
+                );
+
+            let first = token.snapshot().unwrap();
+            token.transfer(accounts.bob, 100);
+            let second = token.snapshot().unwrap();
+            token.transfer(accounts.bob, 200);
+            token.mint_token_by_owner(accounts.alice, 500);
+
+            assert_eq!(token.balance_of_at(accounts.alice, first), Some(1000));
+            assert_eq!(token.balance_of_at(accounts.bob, first), Some(0));
+            assert_eq!(token.balance_of_at(accounts.alice, second), Some(900));
+            assert_eq!(token.balance_of_at(accounts.bob, second), Some(100));
+            assert_eq!(token.balance_of(accounts.bob), 300);
+            assert_eq!(token.total_supply_at(second), Some(1000));
+            assert_eq!(token.total_supply(), 1500);
+            assert_eq!(token.balance_of_at(accounts.alice, 0), None);
+            assert_eq!(token.total_supply_at(second + 1), None);
+
+            // a balance unchanged over many snapshots is found after the first one it changed in
+            for _ in 0..5 {
+                token.snapshot();
+            }
+            token.transfer(accounts.bob, 50);
+            assert_eq!(token.balance_of_at(accounts.bob, second + 3), Some(300));
+            assert_eq!(token.balance_of_at(accounts.bob, second + 5), Some(300));
+        }
+
+        #[ink::test]
+        fn snapshot_permission_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+
+            let mut token = Erc20::new(
+                "SubDAO Token".to_string()
+                , "SDT".to_string()
+                , 1000
+                , 4
+                , accounts.alice
+                );
+
+            set_next_caller(accounts.bob);
+            assert_eq!(token.snapshot(), None);
+            assert_eq!(token.set_snapshotter(Some(accounts.bob)), false);
+
+            set_next_caller(accounts.alice);
+            assert_eq!(token.set_snapshotter(Some(accounts.bob)), true);
+            assert_eq!(token.snapshotter(), Some(accounts.bob));
+            set_next_caller(accounts.bob);
+            assert_eq!(token.snapshot(), Some(1));
+            set_next_caller(accounts.eve);
+            assert_eq!(token.snapshot(), None);
+            assert_eq!(token.current_snapshot_id(), 1);
+        }
+
+        #[ink::test]
+        fn transfer_and_call_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+
+            let mut token = Erc20::new(
+                "SubDAO Token".to_string()
+                , "SDT".to_string()
+                , 1000
+                , 4
+                , accounts.alice
+                );
+
+            mock::reset();
+            mock::set_receiver(accounts.django, true);
+            assert_eq!(token.transfer_and_call(accounts.django, 100, Vec::new()), true);
+            assert_eq!(token.balance_of(accounts.django), 100);
+            assert_eq!(token.balance_of(accounts.alice), 900);
+            // more than the caller holds is not sent
+            assert_eq!(token.transfer_and_call(accounts.django, 1000, Vec::new()), false);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "the receiver didn't accept the transfer")]
+        fn transfer_and_call_rejected_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+
+            let mut token = Erc20::new(
+                "SubDAO Token".to_string()
+                , "SDT".to_string()
+                , 1000
+                , 4
+                , accounts.alice
+                );
+
+            mock::reset();
+            mock::set_receiver(accounts.django, false);
+            token.transfer_and_call(accounts.django, 100, Vec::new());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "the receiver didn't accept the transfer")]
+        fn transfer_and_call_not_contract_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+
+            let mut token = Erc20::new(
+                "SubDAO Token".to_string()
+                , "SDT".to_string()
+                , 1000
+                , 4
+                , accounts.alice
+                );
+
+            mock::reset();
+            token.transfer_and_call(accounts.bob, 100, Vec::new());
+        }
+
+        fn set_next_caller(caller: AccountId) {
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                AccountId::from([0x07; 32]),
+                1_000_000,
+                0,
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])),
+            );
+        }
     }
 }
@@ -12,15 +12,147 @@ mod org {
     use ink_storage::{
         collections::HashMap as StorageHashMap,
     };
+    use ink_storage::traits::{PackedLayout, SpreadLayout};
+    #[cfg(not(test))]
+    use erc20::Erc20;
+
+
+    /// admin of the roles only the owner administers, the owner isn't a role holder
+    pub const OWNER_ROLE: &str = "owner";
+
+    pub const MODERATOR_ROLE: &str = "moderator";
+
+    pub const MEMBER_ROLE: &str = "member";
+
+    /// Most applications `get_applying_members_list` and roles `get_role_list` return.
+    const MAX_PAGE_SIZE: u32 = 50;
+
+    /// Most application ids a page of `get_applying_members_list` looks at.
+    const MAX_PAGE_SCAN: u64 = 500;
+
+    /// selector of the erc20 `transfer_and_call(to: AccountId, value: u64, data: Vec<u8>) -> bool`
+    #[cfg(not(test))]
+    const TRANSFER_AND_CALL_SELECTOR: [u8; 4] = [0xac, 0xd1, 0x0e, 0x50];
+
+    type ApplicationId = u64;
+
+    /// A role and the role whose holders grant and revoke it.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct Role {
+        pub name: String,
+        pub admin_role: String,
+    }
+
+
+    /// A pending application to become a member, with the deposit staked when applying.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct Application {
+        application_id: ApplicationId,
+        applicant: AccountId,
+        name: String,
+        // none if no deposit was staked
+        token_address: Option<AccountId>,
+        stake: u64,
+        apply_time: u64,
+    }
+
+    /// When an account became a member and until when the membership runs.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct Membership {
+        joined_at: u64,
+        // none if it doesn't expire
+        expires_at: Option<u64>,
+    }
+
+    /// A page of `get_applying_members_list`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct ApplicationPage {
+        pub applications: alloc::vec::Vec<Application>,
+        /// Cursor of the next page, none if there are no more applications.
+        pub next_cursor: Option<ApplicationId>,
+    }
+
+    /// A page of `get_role_list`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct RolePage {
+        pub roles: alloc::vec::Vec<Role>,
+        /// Cursor of the next page, none if there are no more roles.
+        pub next_cursor: Option<u32>,
+    }
+
+    /// How accounts become members of the org.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum AdmissionPolicy {
+        /// Accounts join by themselves.
+        Open,
+        /// Accounts apply and a moderator approves them.
+        Application,
+        /// A moderator invites an account, which accepts the invite.
+        InviteOnly,
+        /// Accounts holding at least the minimum balance of the token join by themselves.
+        TokenGated {
+            token_address: AccountId,
+            min_balance: u64,
+        },
+    }
 
 
     #[ink(storage)]
     pub struct OrgManager {
 
-        moderators: StorageHashMap<AccountId, String>,
-        members: StorageHashMap<AccountId, String>,
-        applying_members: StorageHashMap<AccountId, String>,
+        // admin role of each role
+        roles: StorageHashMap<String, String>,
+        // roles by position, the last one takes the position of a role deleted
+        role_list: StorageHashMap<u32, String>,
+        role_positions: StorageHashMap<String, u32>,
+        roles_length: u32,
+        role_holders: StorageHashMap<String, alloc::vec::Vec<AccountId>>,
+        account_roles: StorageHashMap<AccountId, alloc::vec::Vec<String>>,
+        // name of a holder, as given when the role was granted
+        holder_names: StorageHashMap<(String, AccountId), String>,
+        applying_members: StorageHashMap<AccountId, Application>,
+        // applicants of the pending applications by id
+        applicants: StorageHashMap<ApplicationId, AccountId>,
+        // no application below this id is pending
+        applicants_start: ApplicationId,
+        applications_length: u64,
+        // token and amount applicants stake, none if they don't
+        application_deposit: Option<(AccountId, u64)>,
+        // where rejected applicants' deposits and renewal fees go
+        vault_address: Option<AccountId>,
+        // membership of the accounts holding the member role, expired ones included
+        memberships: StorageHashMap<AccountId, Membership>,
+        // how long a membership runs from joining or renewal, 0 if it doesn't expire
+        membership_term: u64,
+        // token and amount a renewal costs, none if renewals are free
+        renewal_fee: Option<(AccountId, u64)>,
+        admission_policy: AdmissionPolicy,
+        // invited account and the moderator who invited it
+        invites: StorageHashMap<AccountId, AccountId>,
         owner: AccountId,
+        // account the owner offered the ownership to and until when it can accept it
+        pending_owner: Option<(AccountId, u64)>,
         org_id:u64,
     }
 
@@ -70,6 +202,136 @@ mod org {
 
 
 
+    #[ink(event)]
+    pub struct RejectDAOMemberEvent {
+        #[ink(topic)]
+        member: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+        #[ink(topic)]
+        rejecter: AccountId,
+        reason: Option<String>,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawApplicationEvent {
+        #[ink(topic)]
+        member: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferProposedEvent {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        pending_owner: AccountId,
+        expire_time: u64,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferCancelledEvent {
+        #[ink(topic)]
+        pending_owner: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferredEvent {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct RenewMembershipEvent {
+        #[ink(topic)]
+        member: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+        expires_at: Option<u64>,
+    }
+
+    #[ink(event)]
+    pub struct AdmissionPolicyChangedEvent {
+        policy: AdmissionPolicy,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct InviteDAOMemberEvent {
+        #[ink(topic)]
+        member: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+        #[ink(topic)]
+        inviter: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RevokeInviteEvent {
+        #[ink(topic)]
+        member: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct CreateRoleEvent {
+        #[ink(topic)]
+        role: String,
+        admin_role: String,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct DeleteRoleEvent {
+        #[ink(topic)]
+        role: String,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct RoleAdminChangedEvent {
+        #[ink(topic)]
+        role: String,
+        admin_role: String,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct RoleGrantedEvent {
+        #[ink(topic)]
+        role: String,
+        #[ink(topic)]
+        account: AccountId,
+        sender: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevokedEvent {
+        #[ink(topic)]
+        role: String,
+        #[ink(topic)]
+        account: AccountId,
+        sender: AccountId,
+        #[ink(topic)]
+        org_id:u64,
+    }
+
     #[ink(event)]
     pub struct ApplyDAOMemberEvent {
         #[ink(topic)]
@@ -93,13 +355,32 @@ mod org {
 
         #[ink(constructor)]
         pub fn new(_owner: AccountId,org_id:u64) -> Self {
-            Self {
+            let mut instance = Self {
                 org_id:org_id,
                 owner:_owner,
-                moderators: StorageHashMap::default(),
-                members: StorageHashMap::default(),
+                pending_owner: None,
+                roles: StorageHashMap::default(),
+                role_list: StorageHashMap::default(),
+                role_positions: StorageHashMap::default(),
+                roles_length: 0,
+                role_holders: StorageHashMap::default(),
+                account_roles: StorageHashMap::default(),
+                holder_names: StorageHashMap::default(),
                 applying_members: StorageHashMap::default(),
-            }
+                applicants: StorageHashMap::default(),
+                applicants_start: 0,
+                applications_length: 0,
+                application_deposit: None,
+                vault_address: None,
+                memberships: StorageHashMap::default(),
+                membership_term: 0,
+                renewal_fee: None,
+                admission_policy: AdmissionPolicy::Application,
+                invites: StorageHashMap::default(),
+            };
+            instance.insert_role(String::from(MODERATOR_ROLE), String::from(OWNER_ROLE));
+            instance.insert_role(String::from(MEMBER_ROLE), String::from(MODERATOR_ROLE));
+            instance
         }
 
 
@@ -116,53 +397,45 @@ mod org {
 
         #[ink(message)]
         pub fn get_dao_moderator_list(&self) -> alloc::vec::Vec<AccountId> {
-            self.moderators.keys();
-            let mut v:alloc::vec::Vec<AccountId> = alloc::vec::Vec::new();
-            for key in self.moderators.keys() {
-                v.push(*key)
-            }
-            v
-
+            self.get_role_holders(String::from(MODERATOR_ROLE))
         }
 
 
         #[ink(message)]
         pub fn get_dao_members_list(&self) -> alloc::vec::Vec<AccountId> {
-            self.members.keys();
-            let mut v:alloc::vec::Vec<AccountId> = alloc::vec::Vec::new();
-            for key in self.members.keys() {
-                v.push(*key)
-            }
-            v
+            self.get_role_holders(String::from(MEMBER_ROLE))
         }
 
 
+        /// number of accounts which are members or moderators
         #[ink(message)]
-        pub fn get_dao_moderator_detail_list(&self) -> alloc::vec::Vec<(AccountId, String)> {
-            self.moderators.keys();
-            let mut v:alloc::vec::Vec<(AccountId, String)> = alloc::vec::Vec::new();
-            for key in self.moderators.keys() {
-
-                let value = self.moderators.get(key).unwrap().clone();
-
-                v.push((*key,value))
-            }
-            v
+        pub fn get_dao_member_count(&self) -> u64 {
+            let members = self.get_dao_members_list();
+            let moderators_not_member = self.get_dao_moderator_list().iter()
+                .filter(|moderator| !self.is_dao_member(**moderator))
+                .count();
+            (members.len() + moderators_not_member) as u64
+        }
 
+        #[ink(message)]
+        pub fn is_dao_moderator(&self, account: AccountId) -> bool {
+            self.has_role(String::from(MODERATOR_ROLE), account)
         }
 
         #[ink(message)]
-        pub fn get_dao_member_detail_list(&self) -> alloc::vec::Vec<(AccountId, String)> {
-            self.members.keys();
-            let mut v:alloc::vec::Vec<(AccountId, String)> = alloc::vec::Vec::new();
-            for key in self.members.keys() {
+        pub fn is_dao_member(&self, account: AccountId) -> bool {
+            self.has_role(String::from(MEMBER_ROLE), account)
+        }
 
-                let value = self.members.get(key).unwrap().clone();
 
-                v.push((*key,value))
-            }
-            v
+        #[ink(message)]
+        pub fn get_dao_moderator_detail_list(&self) -> alloc::vec::Vec<(AccountId, String)> {
+            self.role_detail_list(String::from(MODERATOR_ROLE))
+        }
 
+        #[ink(message)]
+        pub fn get_dao_member_detail_list(&self) -> alloc::vec::Vec<(AccountId, String)> {
+            self.role_detail_list(String::from(MEMBER_ROLE))
         }
 
 
@@ -177,35 +450,38 @@ mod org {
             }
 
 
-            match self.moderators.insert(moderator,name) {
-                Some(_) => { false},
-                None => {
-                    let org_id = self.org_id;
-                    self.env().emit_event(AddDAOModeratorEvent{
-                        moderator,
-                        org_id,});
-                    true
-                }
+            if self.grant(String::from(MODERATOR_ROLE), name, moderator) == false {
+                return false;
             }
+
+            let org_id = self.org_id;
+            self.env().emit_event(AddDAOModeratorEvent{
+                moderator,
+                org_id,});
+            true
         }
 
+        /// add a member whatever the admission policy, the caller has to administer the member role
         #[ink(message)]
         pub fn add_dao_member(&mut self,name:String,member: AccountId) -> bool {
 
+            let caller = self.env().caller();
 
+            if !self.is_role_admin(&String::from(MEMBER_ROLE), caller) {
+                return false;
+            }
 
-            match self.members.insert(member,name) {
-                Some(_) => { false},
-                None => {
-                    let org_id = self.org_id;
-                    self.env().emit_event(AddDAOMemberEvent{
-                        member,
-                        org_id,
-                    });
-                    true
-                }
+            if self.grant(String::from(MEMBER_ROLE), name, member) == false {
+                return false;
             }
 
+            let org_id = self.org_id;
+            self.env().emit_event(AddDAOMemberEvent{
+                member,
+                org_id,
+            });
+            true
+
         }
 
         #[ink(message)]
@@ -217,82 +493,303 @@ mod org {
                 return false;
             }
 
-            match self.moderators.take(&member) {
-                None => { false}
-                Some(_) => {
-                    let org_id = self.org_id;
-                    self.env().emit_event(RemoveDAOModeratorEvent{
-                        moderator:member,
-                        org_id,
-                    });
-                    true
-                }
+            if self.revoke(String::from(MODERATOR_ROLE), member) == false {
+                return false;
             }
 
+            let org_id = self.org_id;
+            self.env().emit_event(RemoveDAOModeratorEvent{
+                moderator:member,
+                org_id,
+            });
+            true
+
 
         }
 
+        /// the caller has to administer the member role
         #[ink(message)]
         pub fn remove_dao_member(&mut self, member: AccountId) -> bool  {
 
-            match self.members.take(&member) {
-                None => { false}
-                Some(_) => {
-                    let org_id = self.org_id;
-                    self.env().emit_event(RemoveDAOMemberEvent{
-                        member:member,
-                        org_id:org_id,
-                    });
-                    true
-                }
+            let caller = self.env().caller();
+
+            if !self.is_role_admin(&String::from(MEMBER_ROLE), caller) {
+                return false;
+            }
+
+            if self.revoke(String::from(MEMBER_ROLE), member) == false {
+                return false;
             }
 
+            let org_id = self.org_id;
+            self.env().emit_event(RemoveDAOMemberEvent{
+                member:member,
+                org_id:org_id,
+            });
+            true
+
         }
 
 
+        /// the caller leaves the org as a member, or as a moderator if it is not a member
         #[ink(message)]
-        pub fn resign(&mut self,member: AccountId) -> bool  {
+        pub fn resign(&mut self) -> bool  {
 
+            let member = self.env().caller();
 
-            if self.members.contains_key(&member) {
-                self.members.take(&member);
+            if self.revoke(String::from(MEMBER_ROLE), member) {
                 return true;
             };
 
-            if self.moderators.contains_key(&member) {
-                self.moderators.take(&member);
+            if self.revoke(String::from(MODERATOR_ROLE), member) {
                 return true;
             };
             return false;
         }
 
+        /// offer the ownership to an account, which has `lifetime` to accept it. A new offer
+        /// replaces the pending one
         #[ink(message)]
-        pub fn transfer_ownership(&mut self,new_owner: AccountId) -> bool  {
+        pub fn transfer_ownership(&mut self,new_owner: AccountId,lifetime: u64) -> bool  {
 
             let caller = self.env().caller();
 
             // only owner can transfer the ownership of the org
-            if caller != self.owner {
+            if caller != self.owner || lifetime == 0 {
                 return false;
             }
 
-            self.owner = new_owner;
+            let expire_time = self.env().block_timestamp().saturating_add(lifetime);
+            self.pending_owner = Some((new_owner, expire_time));
+
+            let org_id = self.org_id;
+            self.env().emit_event(OwnershipTransferProposedEvent{
+                owner: caller,
+                pending_owner: new_owner,
+                expire_time,
+                org_id,
+            });
+            return true;
+        }
+
+        /// become the owner, the caller has to be the pending owner and the offer not expired
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> bool  {
+
+            let caller = self.env().caller();
+
+            match self.pending_owner {
+                Some((pending_owner, expire_time)) if pending_owner == caller && self.env().block_timestamp() < expire_time => {}
+                _ => return false,
+            }
+
+            let previous_owner = self.owner;
+            self.owner = caller;
+            self.pending_owner = None;
+
+            let org_id = self.org_id;
+            self.env().emit_event(OwnershipTransferredEvent{
+                previous_owner,
+                new_owner: caller,
+                org_id,
+            });
+            return true;
+        }
+
+        /// withdraw the pending offer, the caller has to be the owner or the pending owner
+        #[ink(message)]
+        pub fn cancel_ownership_transfer(&mut self) -> bool  {
+
+            let caller = self.env().caller();
+
+            let pending_owner = match self.pending_owner {
+                Some((pending_owner, _)) if caller == self.owner || caller == pending_owner => pending_owner,
+                _ => return false,
+            };
+
+            self.pending_owner = None;
+
+            let org_id = self.org_id;
+            self.env().emit_event(OwnershipTransferCancelledEvent{
+                pending_owner,
+                org_id,
+            });
             return true;
         }
+
+        /// get the pending owner and until when it can accept the ownership
+        #[ink(message)]
+        pub fn get_pending_owner(&self) -> Option<(AccountId, u64)> {
+            self.pending_owner
+        }
+
+        /// apply for the caller to become a member, needs the application policy. If the org
+        /// requires a deposit, the caller has to approve the org to transfer it first
+        #[ink(message)]
+        pub fn apply_member(&mut self,name:String) -> bool {
+
+            let member = self.env().caller();
+
+            if self.admission_policy != AdmissionPolicy::Application
+                || self.memberships.contains_key(&member)
+                || self.applying_members.contains_key(&member) {
+                return false;
+            }
+
+            if let Some((token_address, amount)) = self.application_deposit {
+                if self.token_transfer_from(token_address, member, amount) == false {
+                    return false;
+                }
+            }
+
+            let application_id = self.applications_length;
+            self.applications_length += 1;
+
+            self.applying_members.insert(member, Application{
+                application_id:application_id,
+                applicant:member,
+                name:name,
+                token_address:self.application_deposit.map(|(token_address, _)| token_address),
+                stake:self.application_deposit.map_or(0, |(_, amount)| amount),
+                apply_time:self.env().block_timestamp(),
+            });
+            self.applicants.insert(application_id, member);
+
+            let org_id = self.org_id;
+            self.env().emit_event(ApplyDAOMemberEvent{
+                member,
+                org_id,
+            });
+            true
+
+        }
+
+
+        /// join as a member, needs the open policy, or the token gated one with the caller
+        /// holding at least the minimum balance
         #[ink(message)]
-        pub fn apply_member(&mut self,name:String,member: AccountId) -> bool {
-            match self.applying_members.insert(member,name) {
-                Some(_) => { false},
-                None => {
-                    let org_id = self.org_id;
-                    self.env().emit_event(ApplyDAOMemberEvent{
-                        member,
-                        org_id,
-                    });
-                    true
+        pub fn join(&mut self,name:String) -> bool {
+
+            let member = self.env().caller();
+
+            let can_join = match &self.admission_policy {
+                AdmissionPolicy::Open => true,
+                AdmissionPolicy::TokenGated { token_address, min_balance } => {
+                    self.token_balance(*token_address, member) >= *min_balance
                 }
+                _ => false,
+            };
+
+            if can_join == false || self.grant(String::from(MEMBER_ROLE), name, member) == false {
+                return false;
+            }
+
+            let org_id = self.org_id;
+            self.env().emit_event(AddDAOMemberEvent{
+                member,
+                org_id,
+            });
+            true
+        }
+
+
+        /// invite an account to become a member, needs the invite only policy and the caller to
+        /// administer the member role
+        #[ink(message)]
+        pub fn invite_member(&mut self,member: AccountId) -> bool {
+
+            let caller = self.env().caller();
+
+            if self.admission_policy != AdmissionPolicy::InviteOnly
+                || !self.is_role_admin(&String::from(MEMBER_ROLE), caller)
+                || self.memberships.contains_key(&member) {
+                return false;
+            }
+
+            self.invites.insert(member, caller);
+
+            let org_id = self.org_id;
+            self.env().emit_event(InviteDAOMemberEvent{
+                member,
+                org_id,
+                inviter:caller,
+            });
+            true
+        }
+
+
+        /// the caller has to administer the member role
+        #[ink(message)]
+        pub fn revoke_invite(&mut self,member: AccountId) -> bool {
+
+            let caller = self.env().caller();
+
+            if !self.is_role_admin(&String::from(MEMBER_ROLE), caller) || self.invites.take(&member).is_none() {
+                return false;
             }
 
+            let org_id = self.org_id;
+            self.env().emit_event(RevokeInviteEvent{
+                member,
+                org_id,
+            });
+            true
+        }
+
+
+        /// accept the caller's invite and join as a member, needs the invite only policy
+        #[ink(message)]
+        pub fn accept_invite(&mut self,name:String) -> bool {
+
+            let member = self.env().caller();
+
+            if self.admission_policy != AdmissionPolicy::InviteOnly || !self.invites.contains_key(&member) {
+                return false;
+            }
+
+            if self.grant(String::from(MEMBER_ROLE), name, member) == false {
+                return false;
+            }
+            self.invites.take(&member);
+
+            let org_id = self.org_id;
+            self.env().emit_event(AddDAOMemberEvent{
+                member,
+                org_id,
+            });
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn is_invited(&self,member: AccountId) -> bool {
+            self.invites.contains_key(&member)
+        }
+
+
+        /// only the owner can change the admission policy, pending applications and invites are
+        /// kept but can only be taken up under their policy
+        #[ink(message)]
+        pub fn set_admission_policy(&mut self,policy: AdmissionPolicy) -> bool {
+
+            if self.env().caller() != self.owner {
+                return false;
+            }
+
+            self.admission_policy = policy.clone();
+
+            let org_id = self.org_id;
+            self.env().emit_event(AdmissionPolicyChangedEvent{
+                policy,
+                org_id,
+            });
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_admission_policy(&self) -> AdmissionPolicy {
+            self.admission_policy.clone()
         }
 
 
@@ -315,21 +812,31 @@ mod org {
         }
 
 
+        /// approve an application, the caller has to administer the member role. False if the
+        /// applicant became a member meanwhile, it can still withdraw the application
         #[ink(message)]
         pub fn approve_member(&mut self,name:String,member: AccountId) -> bool {
 
             let caller = self.env().caller();
 
-            let can_operate = self.check_authority(caller);
+            let can_operate = self.is_role_admin(&String::from(MEMBER_ROLE), caller);
 
-            if can_operate == false {
+            if can_operate == false || self.memberships.contains_key(&member) {
                 return false;
             }
 
-            if self.applying_members.contains_key(&member) {
+            if let Some(application) = self.applying_members.get(&member).cloned() {
                 let caller_new = self.env().caller();
-                self.add_dao_member(name,member);
-                self.applying_members.take(&member);
+                // the deposit is refunded
+                if self.return_stake(&application, member) == false {
+                    return false;
+                }
+                self.grant(String::from(MEMBER_ROLE), name, member);
+                self.env().emit_event(AddDAOMemberEvent{
+                    member,
+                    org_id:self.org_id,
+                });
+                self.close_application(member);
                 let org_id = self.org_id;
 
                 self.env().emit_event(ApproveDAOMemberEvent{
@@ -344,12 +851,648 @@ mod org {
 
         }
 
-    }
+
+        /// reject an application, the caller has to administer the member role. The deposit is
+        /// kept by the vault
+        #[ink(message)]
+        pub fn reject_member(&mut self,member: AccountId,reason: Option<String>) -> bool {
+
+            let caller = self.env().caller();
+
+            if !self.is_role_admin(&String::from(MEMBER_ROLE), caller) {
+                return false;
+            }
+
+            let application = match self.applying_members.get(&member) {
+                Some(application) => application.clone(),
+                None => return false,
+            };
+
+            if application.stake > 0 && self.pay_vault(application.token_address.unwrap(), application.stake) == false {
+                return false;
+            }
+
+            self.close_application(member);
+
+            let org_id = self.org_id;
+            self.env().emit_event(RejectDAOMemberEvent{
+                member,
+                org_id,
+                rejecter:caller,
+                reason,
+            });
+            true
+        }
+
+
+        /// withdraw the caller's application, the deposit is refunded
+        #[ink(message)]
+        pub fn withdraw_application(&mut self) -> bool {
+
+            let member = self.env().caller();
+
+            let application = match self.applying_members.get(&member) {
+                Some(application) => application.clone(),
+                None => return false,
+            };
+
+            if self.return_stake(&application, member) == false {
+                return false;
+            }
+
+            self.close_application(member);
+
+            let org_id = self.org_id;
+            self.env().emit_event(WithdrawApplicationEvent{
+                member,
+                org_id,
+            });
+            true
+        }
+
+
+        /// get a page of the pending applications, ordered by id. A page ends after looking at
+        /// MAX_PAGE_SCAN ids, so it may hold fewer applications than the limit
+        #[ink(message)]
+        pub fn get_applying_members_list(&self,cursor: Option<ApplicationId>,limit: u32) -> ApplicationPage {
+            let limit = limit.min(MAX_PAGE_SIZE) as usize;
+            let mut id = cursor.unwrap_or(0).max(self.applicants_start);
+            let end = self.applications_length.min(id.saturating_add(MAX_PAGE_SCAN));
+            let mut applications = alloc::vec::Vec::new();
+            while id < end && applications.len() < limit {
+                if let Some(applicant) = self.applicants.get(&id) {
+                    applications.push(self.applying_members.get(applicant).unwrap().clone());
+                }
+                id += 1;
+            }
+            ApplicationPage {
+                applications,
+                next_cursor: if id < self.applications_length { Some(id) } else { None },
+            }
+        }
+
+
+        #[ink(message)]
+        pub fn get_application(&self,member: AccountId) -> Option<Application> {
+            self.applying_members.get(&member).cloned()
+        }
+
+
+        /// require applicants to stake an amount of the token, refunded on approval or withdrawal
+        /// and kept by the vault on rejection, none to stop requiring it. Only the owner can set it
+        /// and the vault address has to be set first
+        #[ink(message)]
+        pub fn set_application_deposit(&mut self,deposit: Option<(AccountId, u64)>) -> bool {
+
+            if self.env().caller() != self.owner {
+                return false;
+            }
+
+            if deposit.is_some() && self.vault_address.is_none() {
+                return false;
+            }
+
+            self.application_deposit = deposit.filter(|(_, amount)| *amount > 0);
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_application_deposit(&self) -> Option<(AccountId, u64)> {
+            self.application_deposit
+        }
+
+
+        /// renew the caller's membership for a term, from its expiry or from now if it expired,
+        /// paying the renewal fee into the vault. The caller has to approve the org to transfer
+        /// the fee first. The membership stops expiring if memberships don't expire anymore
+        #[ink(message)]
+        pub fn renew_membership(&mut self) -> bool {
+
+            let member = self.env().caller();
+
+            let expires_at = match self.memberships.get(&member) {
+                Some(Membership { expires_at: Some(expires_at), .. }) => *expires_at,
+                _ => return false,
+            };
+
+            if let Some((token_address, amount)) = self.renewal_fee {
+                if self.token_transfer_from(token_address, member, amount) == false {
+                    return false;
+                }
+                if self.pay_vault(token_address, amount) == false {
+                    // give the fee back rather than keep it in the org
+                    self.token_transfer(token_address, member, amount);
+                    return false;
+                }
+            }
+
+            let term = self.membership_term;
+            let expires_at = if term > 0 { Some(expires_at.max(self.env().block_timestamp()).saturating_add(term)) } else { None };
+            self.memberships.get_mut(&member).unwrap().expires_at = expires_at;
+
+            let org_id = self.org_id;
+            self.env().emit_event(RenewMembershipEvent{
+                member,
+                org_id,
+                expires_at,
+            });
+            true
+        }
+
+
+        /// get the membership of an account, expired ones included
+        #[ink(message)]
+        pub fn get_membership(&self,member: AccountId) -> Option<Membership> {
+            self.memberships.get(&member).cloned()
+        }
+
+
+        /// set how long memberships run, 0 for memberships which don't expire, and the renewal
+        /// fee, none for free renewals. Only the owner can set them and the vault address has to
+        /// be set first for a fee. Existing memberships keep their expiry, those which don't
+        /// expire run for a term from now when a term is set
+        #[ink(message)]
+        pub fn set_membership_terms(&mut self,term: u64,renewal_fee: Option<(AccountId, u64)>) -> bool {
+
+            if self.env().caller() != self.owner {
+                return false;
+            }
+
+            if renewal_fee.is_some() && self.vault_address.is_none() {
+                return false;
+            }
+
+            if term > 0 {
+                let expires_at = self.env().block_timestamp().saturating_add(term);
+                for member in self.role_holders.get(&String::from(MEMBER_ROLE)).cloned().unwrap_or_default() {
+                    let membership = self.memberships.get_mut(&member).unwrap();
+                    if membership.expires_at.is_none() {
+                        membership.expires_at = Some(expires_at);
+                    }
+                }
+            }
+
+            self.membership_term = term;
+            self.renewal_fee = renewal_fee.filter(|(_, amount)| *amount > 0);
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_membership_terms(&self) -> (u64, Option<(AccountId, u64)>) {
+            (self.membership_term, self.renewal_fee)
+        }
+
+
+        /// only the owner can set the vault of the org
+        #[ink(message)]
+        pub fn set_vault_address(&mut self,vault_address: AccountId) -> bool {
+
+            if self.env().caller() != self.owner {
+                return false;
+            }
+
+            self.vault_address = Some(vault_address);
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_vault_address(&self) -> Option<AccountId> {
+            self.vault_address
+        }
+
+
+        /// create a role granted and revoked by the holders of the admin role, only the owner
+        /// can create roles. A role can't administer itself, its holders could never be removed
+        /// but by the owner
+        #[ink(message)]
+        pub fn create_role(&mut self,role:String,admin_role:String) -> bool {
+
+            if self.env().caller() != self.owner || role == OWNER_ROLE || self.roles.contains_key(&role) {
+                return false;
+            }
+
+            if admin_role != OWNER_ROLE && !self.roles.contains_key(&admin_role) {
+                return false;
+            }
+
+            self.insert_role(role.clone(), admin_role.clone());
+
+            let org_id = self.org_id;
+            self.env().emit_event(CreateRoleEvent{
+                role,
+                admin_role,
+                org_id,
+            });
+            true
+        }
+
+
+        /// delete a role and revoke it from its holders, only the owner can delete roles. The
+        /// moderator and member roles and the admin roles of other roles can't be deleted
+        #[ink(message)]
+        pub fn delete_role(&mut self,role:String) -> bool {
+
+            if self.env().caller() != self.owner || role == MODERATOR_ROLE || role == MEMBER_ROLE {
+                return false;
+            }
+
+            if self.roles.iter().any(|(name, admin_role)| *admin_role == role && *name != role) {
+                return false;
+            }
+
+            if self.roles.take(&role).is_none() {
+                return false;
+            }
+
+            for holder in self.get_role_holders(role.clone()) {
+                self.revoke(role.clone(), holder);
+            }
+            self.role_holders.take(&role);
+            let position = self.role_positions.take(&role).unwrap();
+            self.roles_length -= 1;
+            let last_role = self.role_list.take(&self.roles_length).unwrap();
+            if position != self.roles_length {
+                self.role_list.insert(position, last_role.clone());
+                self.role_positions.insert(last_role, position);
+            }
+
+            let org_id = self.org_id;
+            self.env().emit_event(DeleteRoleEvent{
+                role,
+                org_id,
+            });
+            true
+        }
+
+
+        /// change the admin role of a role, only the owner can change it, a role can't administer itself
+        #[ink(message)]
+        pub fn set_role_admin(&mut self,role:String,admin_role:String) -> bool {
+
+            if self.env().caller() != self.owner || !self.roles.contains_key(&role) || admin_role == role {
+                return false;
+            }
+
+            if admin_role != OWNER_ROLE && !self.roles.contains_key(&admin_role) {
+                return false;
+            }
+
+            self.roles.insert(role.clone(), admin_role.clone());
+
+            let org_id = self.org_id;
+            self.env().emit_event(RoleAdminChangedEvent{
+                role,
+                admin_role,
+                org_id,
+            });
+            true
+        }
+
+
+        /// grant a role to an account under a name, the caller has to be the owner or hold the
+        /// admin role of the role
+        #[ink(message)]
+        pub fn grant_role(&mut self,role:String,name:String,account:AccountId) -> bool {
+
+            let caller = self.env().caller();
+
+            if !self.is_role_admin(&role, caller) {
+                return false;
+            }
+
+            self.grant(role, name, account)
+        }
+
+
+        /// revoke a role from an account, the caller has to be the owner or hold the admin role
+        /// of the role
+        #[ink(message)]
+        pub fn revoke_role(&mut self,role:String,account:AccountId) -> bool {
+
+            let caller = self.env().caller();
+
+            if !self.is_role_admin(&role, caller) {
+                return false;
+            }
+
+            self.revoke(role, account)
+        }
+
+
+        /// give up a role of the caller
+        #[ink(message)]
+        pub fn renounce_role(&mut self,role:String) -> bool {
+            let caller = self.env().caller();
+            self.revoke(role, caller)
+        }
+
+
+        /// members whose membership expired don't hold the member role
+        #[ink(message)]
+        pub fn has_role(&self,role:String,account:AccountId) -> bool {
+            if role == MEMBER_ROLE && self.is_expired(account) {
+                return false;
+            }
+            self.holder_names.contains_key(&(role, account))
+        }
+
+
+        /// get a page of the roles with their admin roles, from the position of the cursor on.
+        /// The last role takes the place of a role deleted
+        #[ink(message)]
+        pub fn get_role_list(&self,cursor: Option<u32>,limit: u32) -> RolePage {
+            let start = cursor.unwrap_or(0).min(self.roles_length);
+            let end = self.roles_length.min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+            let roles = (start..end)
+                .map(|position| {
+                    let name = self.role_list.get(&position).unwrap();
+                    Role{
+                        name: name.clone(),
+                        admin_role: self.roles.get(name).unwrap().clone(),
+                    }
+                })
+                .collect();
+            RolePage {
+                roles,
+                next_cursor: if end < self.roles_length { Some(end) } else { None },
+            }
+        }
+
+
+        #[ink(message)]
+        pub fn get_account_roles(&self,account:AccountId) -> alloc::vec::Vec<String> {
+            let mut roles = self.account_roles.get(&account).cloned().unwrap_or_default();
+            if self.is_expired(account) {
+                roles.retain(|role| role != MEMBER_ROLE);
+            }
+            roles
+        }
+
+
+        /// get the holders of a role, in the order they were granted it
+        #[ink(message)]
+        pub fn get_role_holders(&self,role:String) -> alloc::vec::Vec<AccountId> {
+            let mut holders = self.role_holders.get(&role).cloned().unwrap_or_default();
+            if role == MEMBER_ROLE {
+                holders.retain(|holder| !self.is_expired(*holder));
+            }
+            holders
+        }
+
+
+        fn is_expired(&self, member: AccountId) -> bool {
+            self.memberships.get(&member)
+                .and_then(|membership| membership.expires_at)
+                .map_or(false, |expires_at| expires_at <= self.env().block_timestamp())
+        }
+
+
+        /// transfer tokens of the org to the vault, recorded as a deposit if the vault lists the
+        /// token and found by the vault's reconciliation otherwise
+        fn pay_vault(&self, token_address: AccountId, amount: u64) -> bool {
+            let vault_address = match self.vault_address {
+                Some(vault_address) => vault_address,
+                None => return false,
+            };
+            self.token_transfer_and_call(token_address, vault_address, amount)
+                || self.token_transfer(token_address, vault_address, amount)
+        }
+
+
+        fn return_stake(&self, application: &Application, to: AccountId) -> bool {
+            if application.stake == 0 {
+                return true;
+            }
+            self.token_transfer(application.token_address.unwrap(), to, application.stake)
+        }
+
+
+        // calls to the token contracts, off-chain tests call the `mock` module instead
+
+        #[cfg(not(test))]
+        fn token_balance(&self, token_address: AccountId, owner: AccountId) -> u64 {
+            let erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            erc20.balance_of(owner)
+        }
+
+
+        #[cfg(not(test))]
+        fn token_transfer(&self, token_address: AccountId, to: AccountId, value: u64) -> bool {
+            let mut erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            erc20.transfer(to, value)
+        }
+
+
+        /// transfer tokens the owner approved the org to transfer to the org
+        #[cfg(not(test))]
+        fn token_transfer_from(&self, token_address: AccountId, from: AccountId, value: u64) -> bool {
+            let mut erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            erc20.transfer_from(from, self.env().account_id(), value)
+        }
+
+
+        /// `transfer_and_call` panics when the receiver rejects the transfer, so it's called
+        /// without the typed reference which would panic along, false if it traps
+        #[cfg(not(test))]
+        fn token_transfer_and_call(&self, token_address: AccountId, to: AccountId, value: u64) -> bool {
+            ink_env::call::build_call::<<Self as ::ink_lang::ContractEnv>::Env>()
+                .callee(token_address)
+                .exec_input(
+                    ink_env::call::ExecutionInput::new(TRANSFER_AND_CALL_SELECTOR.into())
+                        .push_arg(to)
+                        .push_arg(value)
+                        .push_arg(alloc::vec::Vec::<u8>::new()),
+                )
+                .returns::<ink_env::call::utils::ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+
+        #[cfg(test)]
+        fn token_balance(&self, token_address: AccountId, owner: AccountId) -> u64 {
+            mock::balance_of(token_address, owner)
+        }
+
+
+        #[cfg(test)]
+        fn token_transfer(&self, token_address: AccountId, to: AccountId, value: u64) -> bool {
+            mock::transfer(token_address, self.env().account_id(), to, value)
+        }
+
+
+        #[cfg(test)]
+        fn token_transfer_from(&self, token_address: AccountId, from: AccountId, value: u64) -> bool {
+            mock::transfer_from(token_address, self.env().account_id(), from, self.env().account_id(), value)
+        }
+
+
+        #[cfg(test)]
+        fn token_transfer_and_call(&self, token_address: AccountId, to: AccountId, value: u64) -> bool {
+            mock::transfer(token_address, self.env().account_id(), to, value)
+        }
+
+
+        fn close_application(&mut self, member: AccountId) {
+            if let Some(application) = self.applying_members.take(&member) {
+                self.applicants.take(&application.application_id);
+                // move past the closed applications at the start, a bounded number at a time
+                let end = self.applications_length.min(self.applicants_start.saturating_add(MAX_PAGE_SCAN));
+                while self.applicants_start < end && !self.applicants.contains_key(&self.applicants_start) {
+                    self.applicants_start += 1;
+                }
+            }
+        }
+
+
+        fn insert_role(&mut self, role: String, admin_role: String) {
+            self.roles.insert(role.clone(), admin_role);
+            self.role_list.insert(self.roles_length, role.clone());
+            self.role_positions.insert(role, self.roles_length);
+            self.roles_length += 1;
+        }
+
+
+        fn is_role_admin(&self, role: &String, account: AccountId) -> bool {
+            match self.roles.get(role) {
+                Some(admin_role) => account == self.owner || self.has_role(admin_role.clone(), account),
+                None => false,
+            }
+        }
+
+
+        fn role_detail_list(&self, role: String) -> alloc::vec::Vec<(AccountId, String)> {
+            self.get_role_holders(role.clone()).into_iter()
+                .map(|holder| (holder, self.holder_names.get(&(role.clone(), holder)).unwrap().clone()))
+                .collect()
+        }
+
+
+        /// false if the role doesn't exist or the account holds it already, a member whose
+        /// membership expired only comes back by renewing it
+        fn grant(&mut self, role: String, name: String, account: AccountId) -> bool {
+
+            if !self.roles.contains_key(&role) || self.holder_names.contains_key(&(role.clone(), account)) {
+                return false;
+            }
+
+            if self.holder_names.insert((role.clone(), account), name).is_none() {
+                self.role_holders.entry(role.clone()).or_insert(alloc::vec::Vec::new()).push(account);
+                self.account_roles.entry(account).or_insert(alloc::vec::Vec::new()).push(role.clone());
+            }
+
+            if role == MEMBER_ROLE {
+                let joined_at = self.env().block_timestamp();
+                let term = self.membership_term;
+                self.memberships.insert(account, Membership{
+                    joined_at:joined_at,
+                    expires_at:if term > 0 { Some(joined_at.saturating_add(term)) } else { None },
+                });
+            }
+
+            let org_id = self.org_id;
+            self.env().emit_event(RoleGrantedEvent{
+                role,
+                account,
+                sender: self.env().caller(),
+                org_id,
+            });
+            true
+        }
+
+
+        /// false if the account doesn't hold the role
+        fn revoke(&mut self, role: String, account: AccountId) -> bool {
+
+            if self.holder_names.take(&(role.clone(), account)).is_none() {
+                return false;
+            }
+
+            if let Some(holders) = self.role_holders.get_mut(&role) {
+                holders.retain(|holder| *holder != account);
+            }
+            if let Some(roles) = self.account_roles.get_mut(&account) {
+                roles.retain(|name| *name != role);
+            }
+            if role == MEMBER_ROLE {
+                self.memberships.take(&account);
+            }
+
+            let org_id = self.org_id;
+            self.env().emit_event(RoleRevokedEvent{
+                role,
+                account,
+                sender: self.env().caller(),
+                org_id,
+            });
+            true
+        }
+
+    }
 
         
     
 
 
+    /// State of the token contracts the org calls in off-chain tests.
+    #[cfg(test)]
+    mod mock {
+        use super::*;
+        use std::{
+            cell::RefCell,
+            collections::BTreeMap,
+        };
+
+        #[derive(Default)]
+        struct State {
+            balances: BTreeMap<(AccountId, AccountId), u64>,
+            allowances: BTreeMap<(AccountId, AccountId, AccountId), u64>,
+        }
+
+        thread_local! {
+            static STATE: RefCell<State> = RefCell::new(State::default());
+        }
+
+        pub fn reset() {
+            STATE.with(|state| *state.borrow_mut() = State::default());
+        }
+
+        pub fn set_balance(token_address: AccountId, owner: AccountId, value: u64) {
+            STATE.with(|state| state.borrow_mut().balances.insert((token_address, owner), value));
+        }
+
+        pub fn approve(token_address: AccountId, owner: AccountId, spender: AccountId, value: u64) {
+            STATE.with(|state| state.borrow_mut().allowances.insert((token_address, owner, spender), value));
+        }
+
+        pub fn balance_of(token_address: AccountId, owner: AccountId) -> u64 {
+            STATE.with(|state| *state.borrow().balances.get(&(token_address, owner)).unwrap_or(&0))
+        }
+
+        pub fn transfer(token_address: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let from_balance = balance_of(token_address, from);
+            if from_balance < value {
+                return false;
+            }
+            let to_balance = balance_of(token_address, to);
+            set_balance(token_address, from, from_balance - value);
+            set_balance(token_address, to, to_balance + value);
+            true
+        }
+
+        pub fn transfer_from(token_address: AccountId, spender: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let allowance = STATE.with(|state| *state.borrow().allowances.get(&(token_address, from, spender)).unwrap_or(&0));
+            if allowance < value || !transfer(token_address, from, to, value) {
+                return false;
+            }
+            approve(token_address, from, spender, allowance - value);
+            true
+        }
+    }
+
     /// Unit tests
     #[cfg(test)]
     mod tests {
@@ -367,9 +1510,9 @@ mod org {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut org_manager = OrgManager::new(accounts.alice,1);
+            let org_manager = OrgManager::new(accounts.alice,1);
 
-            assert_eq!(org_manager.creator, accounts.alice);
+            assert_eq!(org_manager.get_dao_owner(), accounts.alice);
             assert_eq!(org_manager.org_id, 1);
         }
 
@@ -432,6 +1575,325 @@ mod org {
             assert_eq!(members.len(), 0);
         }
 
+        #[ink::test]
+        fn roles_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            let treasurer = String::from("treasurer");
+            assert!(!org_manager.create_role(treasurer.clone(), treasurer.clone()));
+            assert!(org_manager.create_role(treasurer.clone(), String::from(MODERATOR_ROLE)));
+            assert!(!org_manager.create_role(treasurer.clone(), String::from(MODERATOR_ROLE)));
+            assert!(!org_manager.set_role_admin(treasurer.clone(), treasurer.clone()));
+            assert!(org_manager.grant_role(treasurer.clone(), String::from("bob"), accounts.bob));
+            assert!(org_manager.add_dao_moderator(String::from("eve"), accounts.eve));
+            assert!(org_manager.has_role(treasurer.clone(), accounts.bob));
+            assert_eq!(org_manager.get_account_roles(accounts.eve), vec![String::from(MODERATOR_ROLE)]);
+
+            // eve is a moderator, the admin of treasurers, bob is not
+            set_next_caller(accounts.bob);
+            assert!(!org_manager.grant_role(treasurer.clone(), String::from("charlie"), accounts.charlie));
+            set_next_caller(accounts.eve);
+            assert!(org_manager.grant_role(treasurer.clone(), String::from("charlie"), accounts.charlie));
+            assert_eq!(org_manager.get_role_holders(treasurer.clone()), vec![accounts.bob, accounts.charlie]);
+            set_next_caller(accounts.eve);
+            assert!(org_manager.revoke_role(treasurer.clone(), accounts.bob));
+            assert_eq!(org_manager.get_account_roles(accounts.bob), Vec::<String>::new());
+
+            set_next_caller(accounts.alice);
+            assert!(!org_manager.delete_role(String::from(MEMBER_ROLE)));
+            set_next_caller(accounts.alice);
+            assert!(org_manager.delete_role(treasurer.clone()));
+            assert!(!org_manager.has_role(treasurer, accounts.charlie));
+            let page = org_manager.get_role_list(None, 10);
+            assert_eq!((page.roles.len(), page.next_cursor), (2, None));
+            let page = org_manager.get_role_list(None, 1);
+            assert_eq!((page.roles[0].name.as_str(), page.next_cursor), (MODERATOR_ROLE, Some(1)));
+            let page = org_manager.get_role_list(page.next_cursor, 1);
+            assert_eq!((page.roles[0].name.as_str(), page.next_cursor), (MEMBER_ROLE, None));
+        }
+
+        #[ink::test]
+        fn admission_policies_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            assert_eq!(org_manager.get_admission_policy(), AdmissionPolicy::Application);
+
+            set_next_caller(accounts.bob);
+            assert!(!org_manager.add_dao_member(String::from("bob"), accounts.bob));
+            assert!(!org_manager.join(String::from("bob")));
+            assert!(org_manager.apply_member(String::from("bob")));
+            set_next_caller(accounts.alice);
+            assert!(org_manager.approve_member(String::from("bob"), accounts.bob));
+            assert!(org_manager.is_dao_member(accounts.bob));
+
+            assert!(org_manager.set_admission_policy(AdmissionPolicy::Open));
+            set_next_caller(accounts.charlie);
+            assert!(!org_manager.apply_member(String::from("charlie")));
+            assert!(org_manager.join(String::from("charlie")));
+
+            set_next_caller(accounts.alice);
+            assert!(org_manager.set_admission_policy(AdmissionPolicy::InviteOnly));
+            assert!(org_manager.invite_member(accounts.eve));
+            set_next_caller(accounts.django);
+            assert!(!org_manager.join(String::from("django")));
+            assert!(!org_manager.apply_member(String::from("django")));
+            assert!(!org_manager.accept_invite(String::from("django")));
+            set_next_caller(accounts.eve);
+            assert!(!org_manager.set_admission_policy(AdmissionPolicy::Open));
+            assert!(org_manager.accept_invite(String::from("eve")));
+            assert!(!org_manager.is_invited(accounts.eve));
+            assert_eq!(org_manager.get_dao_members_list(), vec![accounts.bob, accounts.charlie, accounts.eve]);
+        }
+
+        #[ink::test]
+        fn token_gated_join_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            mock::reset();
+            let token_address = AccountId::from([0x09; 32]);
+            mock::set_balance(token_address, accounts.bob, 100);
+            mock::set_balance(token_address, accounts.charlie, 99);
+            assert!(org_manager.set_admission_policy(AdmissionPolicy::TokenGated{token_address, min_balance: 100}));
+
+            set_next_caller(accounts.charlie);
+            assert!(!org_manager.join(String::from("charlie")));
+            assert!(!org_manager.apply_member(String::from("charlie")));
+            set_next_caller(accounts.bob);
+            assert!(org_manager.join(String::from("bob")));
+            assert!(!org_manager.join(String::from("bob")));
+            assert_eq!(org_manager.get_dao_members_list(), vec![accounts.bob]);
+        }
+
+        #[ink::test]
+        fn applications_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            for (applicant, name) in &[(accounts.bob, "bob"), (accounts.charlie, "charlie"), (accounts.eve, "eve")] {
+                set_next_caller(*applicant);
+                assert!(org_manager.apply_member(String::from(*name)));
+            }
+            let applicants = |page: &ApplicationPage| page.applications.iter().map(|application| application.applicant).collect::<Vec<_>>();
+            let page = org_manager.get_applying_members_list(None, 2);
+            assert_eq!((applicants(&page), page.next_cursor), (vec![accounts.bob, accounts.charlie], Some(2)));
+
+            set_next_caller(accounts.charlie);
+            assert!(org_manager.withdraw_application());
+            assert!(!org_manager.reject_member(accounts.bob, None));
+            set_next_caller(accounts.alice);
+            assert!(org_manager.reject_member(accounts.bob, Some(String::from("spam"))));
+            assert!(!org_manager.approve_member(String::from("bob"), accounts.bob));
+            assert!(!org_manager.is_dao_member(accounts.bob));
+
+            let page = org_manager.get_applying_members_list(None, 2);
+            assert_eq!((applicants(&page), page.next_cursor), (vec![accounts.eve], None));
+            assert!(org_manager.get_application(accounts.charlie).is_none());
+        }
+
+        #[ink::test]
+        fn application_deposits_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            mock::reset();
+            let org_address = AccountId::from([0x07; 32]);
+            let token_address = AccountId::from([0x09; 32]);
+            let vault_address = accounts.django;
+            assert!(!org_manager.set_application_deposit(Some((token_address, 10))));
+            assert!(org_manager.set_vault_address(vault_address));
+            assert!(org_manager.set_application_deposit(Some((token_address, 10))));
+            for applicant in &[accounts.bob, accounts.charlie, accounts.eve, accounts.frank] {
+                mock::set_balance(token_address, *applicant, 10);
+                mock::approve(token_address, *applicant, org_address, 10);
+            }
+
+            // the deposit is staked when applying, without an allowance there is no application
+            mock::approve(token_address, accounts.frank, org_address, 0);
+            for (applicant, name) in &[(accounts.bob, "bob"), (accounts.charlie, "charlie"), (accounts.eve, "eve"), (accounts.frank, "frank")] {
+                set_next_caller(*applicant);
+                assert_eq!(org_manager.apply_member(String::from(*name)), *applicant != accounts.frank);
+            }
+            assert_eq!(org_manager.get_application(accounts.bob).unwrap().stake, 10);
+            assert_eq!(org_manager.get_application(accounts.frank), None);
+            assert_eq!(mock::balance_of(token_address, org_address), 30);
+
+            // refunded on withdrawal and approval, kept by the vault on rejection
+            set_next_caller(accounts.bob);
+            assert!(org_manager.withdraw_application());
+            assert_eq!(mock::balance_of(token_address, accounts.bob), 10);
+            set_next_caller(accounts.alice);
+            assert!(org_manager.approve_member(String::from("charlie"), accounts.charlie));
+            assert_eq!(mock::balance_of(token_address, accounts.charlie), 10);
+            assert!(org_manager.reject_member(accounts.eve, None));
+            assert_eq!(mock::balance_of(token_address, accounts.eve), 0);
+            assert_eq!(mock::balance_of(token_address, vault_address), 10);
+            assert_eq!(mock::balance_of(token_address, org_address), 0);
+        }
+
+        #[ink::test]
+        fn approve_member_fails_for_members() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            mock::reset();
+            let org_address = AccountId::from([0x07; 32]);
+            let token_address = AccountId::from([0x09; 32]);
+            assert!(org_manager.set_vault_address(accounts.django));
+            assert!(org_manager.set_application_deposit(Some((token_address, 10))));
+            mock::set_balance(token_address, accounts.bob, 10);
+            mock::approve(token_address, accounts.bob, org_address, 10);
+            set_next_caller(accounts.bob);
+            assert!(org_manager.apply_member(String::from("bob")));
+
+            // bob is added meanwhile, the approval neither refunds nor closes the application
+            set_next_caller(accounts.alice);
+            assert!(org_manager.add_dao_member(String::from("bob"), accounts.bob));
+            assert!(!org_manager.approve_member(String::from("bob"), accounts.bob));
+            assert_eq!(mock::balance_of(token_address, org_address), 10);
+            assert!(org_manager.get_application(accounts.bob).is_some());
+            set_next_caller(accounts.bob);
+            assert!(org_manager.withdraw_application());
+            assert_eq!(mock::balance_of(token_address, accounts.bob), 10);
+        }
+
+        #[ink::test]
+        fn memberships_expire() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            assert!(org_manager.add_dao_member(String::from("bob"), accounts.bob));
+            assert_eq!(org_manager.get_membership(accounts.bob), Some(Membership{joined_at: 0, expires_at: None}));
+            // timestamps advance by 5 a block
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert!(org_manager.set_membership_terms(10, None));
+            assert!(org_manager.add_dao_member(String::from("eve"), accounts.eve));
+            // bob's membership runs for a term from when the term was set
+            assert_eq!(org_manager.get_membership(accounts.bob), Some(Membership{joined_at: 0, expires_at: Some(15)}));
+            assert_eq!(org_manager.get_membership(accounts.eve), Some(Membership{joined_at: 5, expires_at: Some(15)}));
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert!(!org_manager.is_dao_member(accounts.eve));
+            assert_eq!(org_manager.get_dao_members_list(), Vec::<AccountId>::new());
+            assert_eq!(org_manager.get_dao_member_count(), 0);
+
+            // expired members don't come back but by renewing
+            assert!(!org_manager.add_dao_member(String::from("eve"), accounts.eve));
+            assert!(org_manager.set_admission_policy(AdmissionPolicy::Open));
+            set_next_caller(accounts.eve);
+            assert!(!org_manager.join(String::from("eve")));
+            set_next_caller(accounts.charlie);
+            assert!(!org_manager.renew_membership());
+            set_next_caller(accounts.eve);
+            assert!(org_manager.renew_membership());
+            assert_eq!(org_manager.get_membership(accounts.eve), Some(Membership{joined_at: 5, expires_at: Some(25)}));
+            assert_eq!(org_manager.get_dao_members_list(), vec![accounts.eve]);
+
+            // an expired member can be removed
+            set_next_caller(accounts.alice);
+            assert!(org_manager.remove_dao_member(accounts.bob));
+            assert_eq!(org_manager.get_membership(accounts.bob), None);
+            assert_eq!(org_manager.get_role_holders(String::from(MEMBER_ROLE)), vec![accounts.eve]);
+            set_next_caller(accounts.bob);
+            assert!(!org_manager.renew_membership());
+            assert!(org_manager.join(String::from("bob")));
+        }
+
+        #[ink::test]
+        fn renewal_fees_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            mock::reset();
+            let org_address = AccountId::from([0x07; 32]);
+            let token_address = AccountId::from([0x09; 32]);
+            assert!(!org_manager.set_membership_terms(10, Some((token_address, 5))));
+            assert!(org_manager.set_vault_address(accounts.django));
+            assert!(org_manager.set_membership_terms(10, Some((token_address, 5))));
+            assert!(org_manager.add_dao_member(String::from("eve"), accounts.eve));
+            mock::set_balance(token_address, accounts.eve, 5);
+
+            // the fee is paid before expiring too, the term runs on from the expiry
+            set_next_caller(accounts.eve);
+            assert!(!org_manager.renew_membership());
+            mock::approve(token_address, accounts.eve, org_address, 5);
+            set_next_caller(accounts.eve);
+            assert!(org_manager.renew_membership());
+            assert_eq!(org_manager.get_membership(accounts.eve), Some(Membership{joined_at: 0, expires_at: Some(20)}));
+            assert_eq!(mock::balance_of(token_address, accounts.eve), 0);
+            assert_eq!(mock::balance_of(token_address, org_address), 0);
+            assert_eq!(mock::balance_of(token_address, accounts.django), 5);
+            set_next_caller(accounts.eve);
+            assert!(!org_manager.renew_membership());
+        }
+
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut org_manager = OrgManager::new(accounts.alice,1);
+            assert!(org_manager.transfer_ownership(accounts.bob, 10));
+            assert_eq!(org_manager.get_dao_owner(), accounts.alice);
+            assert_eq!(org_manager.get_pending_owner(), Some((accounts.bob, 10)));
+
+            set_next_caller(accounts.eve);
+            assert!(!org_manager.accept_ownership());
+            // timestamps advance by 5 a block
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            set_next_caller(accounts.bob);
+            assert!(!org_manager.accept_ownership());
+
+            set_next_caller(accounts.alice);
+            assert!(org_manager.transfer_ownership(accounts.bob, 10));
+            set_next_caller(accounts.bob);
+            assert!(org_manager.accept_ownership());
+            assert_eq!(org_manager.get_dao_owner(), accounts.bob);
+            assert_eq!(org_manager.get_pending_owner(), None);
+            set_next_caller(accounts.alice);
+            assert!(!org_manager.transfer_ownership(accounts.alice, 10));
+
+            // a vote manager accepts through the action of a passed vote, the offer never expires
+            let vote_manager = AccountId::from([0x09; 32]);
+            set_next_caller(accounts.bob);
+            assert!(org_manager.transfer_ownership(vote_manager, u64::MAX));
+            assert_eq!(org_manager.get_pending_owner(), Some((vote_manager, u64::MAX)));
+            set_next_caller(vote_manager);
+            assert!(org_manager.accept_ownership());
+            assert_eq!(org_manager.get_dao_owner(), vote_manager);
+        }
+
+        fn set_next_caller(caller: AccountId) {
+            test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                AccountId::from([0x07; 32]),
+                1_000_000,
+                0,
+                test::CallData::new(call::Selector::new([0x00; 4])),
+            );
+        }
+
         #[ink::test]
         fn resign_works() {
             let accounts =
@@ -443,14 +1905,27 @@ mod org {
             org_manager.add_dao_member(bob_name,accounts.bob);
             let eve_name = String::from("eve");
             org_manager.add_dao_member(eve_name,accounts.eve);
+            let eve_name = String::from("eve");
+            org_manager.add_dao_moderator(eve_name,accounts.eve);
             let mut members = org_manager.get_dao_members_list();
             assert_eq!(members.len(), 2);
-            org_manager.resign(accounts.bob);
+            // only the caller resigns
+            set_next_caller(accounts.bob);
+            assert!(org_manager.resign());
+            assert!(!org_manager.resign());
             members = org_manager.get_dao_members_list();
-            assert_eq!(members.len(), 1);
-            org_manager.resign(accounts.eve);
+            assert_eq!(members, vec![accounts.eve]);
+            // eve resigns as a member, then as a moderator
+            set_next_caller(accounts.eve);
+            assert!(org_manager.resign());
+            assert!(org_manager.is_dao_moderator(accounts.eve));
             members = org_manager.get_dao_members_list();
             assert_eq!(members.len(), 0);
+            set_next_caller(accounts.eve);
+            assert!(org_manager.resign());
+            assert!(!org_manager.is_dao_moderator(accounts.eve));
+            set_next_caller(accounts.charlie);
+            assert!(!org_manager.resign());
         }
     }
 }
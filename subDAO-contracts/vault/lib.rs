@@ -20,20 +20,336 @@ mod vault {
     use org::OrgManager;
     use auth::Auth;
 
+    /// Token name of the native currency in the transfer history.
+    const NATIVE_TOKEN_NAME: &str = "native";
+
+    type RequestId = u64;
+    type StreamId = u64;
+    type PayrollId = u64;
+    type TransferId = u64;
+    type QueueId = u64;
+
+    /// Most transfers returned by a page.
+    const MAX_PAGE_SIZE: u32 = 50;
+    /// Most transfers looked at by a page, the page is cut short by filters matching few transfers.
+    const MAX_PAGE_SCAN: u32 = 500;
+    /// Default period withdrawals add up in against their threshold, a day in milliseconds.
+    const DEFAULT_THRESHOLD_PERIOD: u64 = 24 * 60 * 60 * 1000;
+    /// Most role budgets of a token, a withdrawal checks the caller's permission for each.
+    const MAX_ROLE_BUDGETS: usize = 5;
+
+    /// How a transfer moves balance of the vault.
     #[derive(
-    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,Default
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum Direction {
+        /// Withdrawn from the vault.
+        Out,
+        /// Deposited to the vault.
+        In,
+        /// Reserved for a stream, to its recipient.
+        Reserve,
+        /// Released to the vault by a cancelled stream.
+        Release,
+        /// Found in the vault by `reconcile` without a recorded deposit.
+        ExternalIn,
+        /// Missing from the vault when `reconcile` ran, without a recorded withdrawal.
+        ExternalOut,
+    }
+
+    /// Secondary indexes of the transfer history.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    enum TransferIndex {
+        Token(Option<AccountId>),
+        Counterparty(AccountId),
+        Direction(Direction),
+    }
+
+    /// Filters of `get_transfers`, a transfer has to match all those set.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct TransferFilter {
+        /// `Some(None)` for the native currency.
+        pub token_address: Option<Option<AccountId>>,
+        /// The account the vault transfers with.
+        pub counterparty: Option<AccountId>,
+        pub direction: Option<Direction>,
+        /// Transfers at or after this time.
+        pub from_time: Option<u64>,
+        /// Transfers before this time.
+        pub to_time: Option<u64>,
+    }
+
+    /// A page of transfers ordered by id, and the cursor to query the next page from.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct TransferPage {
+        pub transfers: ink_prelude::vec::Vec<Transfer>,
+        /// None if there are no more transfers.
+        pub next_cursor: Option<TransferId>,
+    }
+
+    /// Indicates whether a withdraw request is already confirmed or needs further confirmations.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug, ::scale_info::TypeInfo))]
+    pub enum ConfirmationStatus {
+        /// The request is already confirmed.
+        Confirmed,
+        /// Indicates how many confirmations are remaining.
+        ConfirmationsNeeded(u32),
+    }
+
+    #[derive(
+    Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum TokenStatus {
+        /// In the token list, deposits and withdrawals are accepted.
+        Visible,
+        /// Out of the token list, only deposits are accepted.
+        Hidden,
+        /// Not supported anymore, neither deposits nor withdrawals are accepted.
+        Delisted,
+    }
+
+    /// A token of the vault's registry, with its metadata cached when it was listed.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct TokenInfo {
+        token_address: AccountId,
+        name: String,
+        symbol: String,
+        decimals: u8,
+        status: TokenStatus,
+    }
+
+    /// A listed token and the vault's balance of it.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct TokenDetails {
+        pub token: TokenInfo,
+        pub balance: u64,
+        /// Part of the balance reserved for streams.
+        pub reserved: Balance,
+    }
+
+    /// The balance of a token, none for the native currency, as recorded by the transfer history
+    /// and as held by the vault.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub struct Discrepancy {
+        pub token_address: Option<AccountId>,
+        pub recorded: Balance,
+        pub actual: Balance,
+    }
+
+    /// Who a budget is for.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum BudgetHolder {
+        Account(AccountId),
+        /// The accounts with the permission for the action in the auth contract, sharing the budget.
+        Role {
+            contract_name: String,
+            function_name: String,
+        },
+    }
+
+    /// An amount of a token, none for the native currency, its holder may withdraw every period.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct Budget {
+        holder: BudgetHolder,
+        token_address: Option<AccountId>,
+        amount: Balance,
+        // length of a period, in block timestamp
+        period: u64,
+        // periods follow each other from the time the budget was set
+        start_time: u64,
+        period_start: u64,
+        // withdrawn in the period starting at period_start
+        spent: Balance,
+    }
+
+    /// A payment of a token, none for the native currency, vesting linearly from the start to
+    /// the end time, nothing is vested before the cliff.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct Stream {
+        stream_id: StreamId,
+        recipient: AccountId,
+        token_address: Option<AccountId>,
+        total: Balance,
+        start_time: u64,
+        end_time: u64,
+        cliff_time: Option<u64>,
+        claimed: Balance,
+        // the vested amount was paid and the rest released when cancelled
+        cancelled: bool,
+    }
+
+    /// A payment of the same amount of a token, none for the native currency, to the recipient
+    /// every interval, paid by `process_due_payments` from its next due time on.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct PayrollEntry {
+        payroll_id: PayrollId,
+        recipient: AccountId,
+        token_address: Option<AccountId>,
+        amount: Balance,
+        interval: u64,
+        next_due: u64,
+    }
+
+    #[derive(
+    Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum QueuedWithdrawalState {
+        Queued,
+        Executed,
+        Cancelled,
+    }
+
+    /// What a withdraw request or a queued withdrawal does once it runs, to its `to_address`.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub enum WithdrawalKind {
+        /// Transfer the value.
+        Transfer,
+        /// Reserve the value for a stream.
+        Stream {
+            start_time: u64,
+            end_time: u64,
+            cliff_time: Option<u64>,
+        },
+        /// Add a payroll entry paying the value every interval, due from the first due time or
+        /// from when it runs if that is later.
+        Payroll {
+            interval: u64,
+            first_due: u64,
+        },
+    }
+
+    /// How a withdrawal went through.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum WithdrawOutcome {
+        /// Done at once, with the id of the transfer, or of the stream or payroll entry created.
+        Done(u64),
+        /// Waiting for confirmations, see `confirm_withdraw_request`.
+        Requested(RequestId),
+        /// Waiting for the timelock delay, see `execute_queued_withdrawal`.
+        Queued(QueueId),
+    }
+
+    /// A withdrawal waiting for the timelock delay, it can be cancelled until its eta and
+    /// executed by anyone from then on.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct QueuedWithdrawal {
+        queue_id: QueueId,
+        // none for the native currency
+        token_address: Option<AccountId>,
+        to_address: AccountId,
+        value: Balance,
+        kind: WithdrawalKind,
+        queued_by: AccountId,
+        queue_time: u64,
+        // can be executed from then on
+        eta: u64,
+        state: QueuedWithdrawalState,
+    }
+
+    /// A withdrawal above the threshold of its token, it runs once enough moderators confirmed it.
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
+    )]
+    #[cfg_attr(
+    feature = "std",
+    derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
+    )]
+    pub struct WithdrawRequest {
+        request_id: RequestId,
+        // none for the native currency
+        token_address: Option<AccountId>,
+        to_address: AccountId,
+        value: Balance,
+        kind: WithdrawalKind,
+        submitter: AccountId,
+        submit_time: u64,
+        // can't be confirmed or executed from then on, none if it doesn't expire
+        expire_time: Option<u64>,
+        executed: bool,
+    }
+
+    #[derive(
+    Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout
     )]
     #[cfg_attr(
     feature = "std",
     derive(::scale_info::TypeInfo, ::ink_storage::traits::StorageLayout)
     )]
     pub struct Transfer {
-        transfer_id:u64,
-        transfer_direction:u64,// 1: out 2 : in
+        transfer_id:TransferId,
+        transfer_direction:Direction,
+        // none for the native currency
+        token_address: Option<AccountId>,
         token_name: String,
         from_address:AccountId,
         to_address:AccountId,
-        value: u64,
+        value: Balance,
         transfer_time:u64,
     }
 
@@ -44,12 +360,59 @@ mod vault {
     #[ink(storage)]
     pub struct VaultManager {
 
-        tokens: StorageHashMap<AccountId, AccountId>,
-        visible_tokens: StorageHashMap<AccountId, AccountId>,
-        transfer_history:StorageHashMap<u64,Transfer>,
+        tokens: StorageHashMap<AccountId, TokenInfo>,
+        // registered tokens in the order they were first added
+        token_list: ink_prelude::vec::Vec<AccountId>,
+        transfer_history:StorageHashMap<TransferId,Transfer>,
+        // id of the latest transfer, ids start from 1
+        transfer_count:TransferId,
+        // ids of the transfers in an index, ascending by position
+        transfer_index:StorageHashMap<(TransferIndex,u64),TransferId>,
+        transfer_index_length:StorageHashMap<TransferIndex,u64>,
+        // net of the recorded transfers in and out of a token, none for the native currency
+        recorded_balances:StorageHashMap<Option<AccountId>,Balance>,
         org_contract_address:AccountId,
         vault_contract_address:AccountId,
         auth_contract_address:AccountId,
+        // withdrawals above the threshold of their token, none for the native currency, are requests
+        withdraw_thresholds: StorageHashMap<Option<AccountId>, Balance>,
+        // length of the periods the withdrawals without confirmations add up in against the threshold
+        threshold_period: u64,
+        // start of the current period and what was withdrawn in it without confirmations, by token
+        unconfirmed_withdrawals: StorageHashMap<Option<AccountId>, (u64, Balance)>,
+        // moderators of the org other than the submitter who have to confirm a withdraw request
+        required_confirmations: u32,
+        // how long a withdraw request can be confirmed and executed, 0 if it doesn't expire
+        request_lifetime: u64,
+        withdraw_requests: StorageHashMap<RequestId, WithdrawRequest>,
+        withdraw_requests_length: u64,
+        request_confirmations: StorageHashMap<(RequestId, AccountId), ()>,
+        budgets: StorageHashMap<(BudgetHolder, Option<AccountId>), Budget>,
+        // keys of the budgets by position, the last one takes the position of a budget removed
+        budget_keys: StorageHashMap<u32, (BudgetHolder, Option<AccountId>)>,
+        budget_positions: StorageHashMap<(BudgetHolder, Option<AccountId>), u32>,
+        budgets_length: u32,
+        // holders of the role budgets of a token, none for the native currency
+        role_budgets: StorageHashMap<Option<AccountId>, ink_prelude::vec::Vec<BudgetHolder>>,
+        streams: StorageHashMap<StreamId, Stream>,
+        streams_length: u64,
+        // balance of a token, none for the native currency, owed to streams and not withdrawable
+        reserved: StorageHashMap<Option<AccountId>, Balance>,
+        // how long withdrawals wait in the queue, 0 if they are not queued
+        timelock_delay: u64,
+        // only withdrawals of a token above its limit are queued, all of them if it has none
+        timelock_limits: StorageHashMap<Option<AccountId>, Balance>,
+        queued_withdrawals: StorageHashMap<QueueId, QueuedWithdrawal>,
+        queued_withdrawals_length: u64,
+        payroll: StorageHashMap<PayrollId, PayrollEntry>,
+        payroll_length: u64,
+        // ids of the payroll entries, in the order they were added
+        // ids of the payroll entries by position, the last one takes the position of an entry removed
+        payroll_ids: StorageHashMap<u32, PayrollId>,
+        payroll_positions: StorageHashMap<PayrollId, u32>,
+        payroll_ids_length: u32,
+        // position in `payroll_ids` the next `process_due_payments` starts from
+        payroll_cursor: u32,
     }
 
     /// Errors that can occur upon calling this contract.
@@ -79,6 +442,20 @@ mod vault {
 
 
 
+    #[ink(event)]
+    pub struct UnhideVaultTokenEvent {
+        #[ink(topic)]
+        token_address: AccountId,
+
+    }
+
+    #[ink(event)]
+    pub struct DelistVaultTokenEvent {
+        #[ink(topic)]
+        token_address: AccountId,
+
+    }
+
     #[ink(event)]
     pub struct GetTokenBalanceEvent {
         #[ink(topic)]
@@ -113,278 +490,1892 @@ mod vault {
         value:u64,
     }
 
+    #[ink(event)]
+    pub struct SubmitWithdrawRequestEvent {
+        #[ink(topic)]
+        request_id:RequestId,
+
+        #[ink(topic)]
+        submitter:AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ConfirmWithdrawRequestEvent {
+        #[ink(topic)]
+        request_id:RequestId,
+
+        #[ink(topic)]
+        moderator:AccountId,
+
+        status:ConfirmationStatus,
+    }
+
+    #[ink(event)]
+    pub struct RevokeWithdrawRequestEvent {
+        #[ink(topic)]
+        request_id:RequestId,
+
+        #[ink(topic)]
+        moderator:AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ExecuteWithdrawRequestEvent {
+        #[ink(topic)]
+        request_id:RequestId,
+    }
+
+    #[ink(event)]
+    pub struct QueueWithdrawalEvent {
+        #[ink(topic)]
+        queue_id:QueueId,
+
+        #[ink(topic)]
+        to_address:AccountId,
+
+        value:Balance,
+
+        eta:u64,
+    }
+
+    #[ink(event)]
+    pub struct CancelQueuedWithdrawalEvent {
+        #[ink(topic)]
+        queue_id:QueueId,
+    }
+
+    #[ink(event)]
+    pub struct ExecuteQueuedWithdrawalEvent {
+        #[ink(topic)]
+        queue_id:QueueId,
+    }
+
+    #[ink(event)]
+    pub struct CreateStreamEvent {
+        #[ink(topic)]
+        stream_id:StreamId,
+
+        #[ink(topic)]
+        recipient:AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ClaimStreamEvent {
+        #[ink(topic)]
+        stream_id:StreamId,
+
+        value:Balance,
+    }
+
+    #[ink(event)]
+    pub struct CancelStreamEvent {
+        #[ink(topic)]
+        stream_id:StreamId,
+
+        // unvested balance released to the vault
+        remainder:Balance,
+    }
+
+    #[ink(event)]
+    pub struct AddPayrollEvent {
+        #[ink(topic)]
+        payroll_id:PayrollId,
+
+        #[ink(topic)]
+        recipient:AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RemovePayrollEvent {
+        #[ink(topic)]
+        payroll_id:PayrollId,
+    }
+
+    #[ink(event)]
+    pub struct PayrollPaidEvent {
+        #[ink(topic)]
+        payroll_id:PayrollId,
+
+        value:Balance,
+
+        due_time:u64,
+    }
+
+    #[ink(event)]
+    pub struct PayrollSkippedEvent {
+        #[ink(topic)]
+        payroll_id:PayrollId,
+
+        value:Balance,
+
+        due_time:u64,
+    }
+
+    #[ink(event)]
+    pub struct ReconcileEvent {
+        #[ink(topic)]
+        token_address:Option<AccountId>,
+
+        transfer_direction:Direction,
+
+        value:Balance,
+    }
+
+    #[ink(event)]
+    pub struct DepositNativeEvent {
+        #[ink(topic)]
+        from_address:AccountId,
+
+        value:Balance,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawNativeEvent {
+        #[ink(topic)]
+        to_address:AccountId,
+
+        value:Balance,
+    }
+
+
+
+
+
+    impl VaultManager {
+
+        #[ink(constructor)]
+        pub fn new(org_contract_address: AccountId,auth_contract_address: AccountId) -> Self {
+
+            let vault_contract_address = Self::env().account_id();
+
+            Self {
+                org_contract_address:org_contract_address,
+                auth_contract_address:auth_contract_address,
+                tokens: StorageHashMap::default(),
+                token_list: ink_prelude::vec::Vec::new(),
+                transfer_history: StorageHashMap::default(),
+                transfer_count: 0,
+                transfer_index: StorageHashMap::default(),
+                transfer_index_length: StorageHashMap::default(),
+                recorded_balances: StorageHashMap::default(),
+                vault_contract_address: vault_contract_address,
+                withdraw_thresholds: StorageHashMap::default(),
+                threshold_period: DEFAULT_THRESHOLD_PERIOD,
+                unconfirmed_withdrawals: StorageHashMap::default(),
+                required_confirmations: 1,
+                request_lifetime: 0,
+                withdraw_requests: StorageHashMap::default(),
+                withdraw_requests_length: 0,
+                request_confirmations: StorageHashMap::default(),
+                budgets: StorageHashMap::default(),
+                budget_keys: StorageHashMap::default(),
+                budget_positions: StorageHashMap::default(),
+                budgets_length: 0,
+                role_budgets: StorageHashMap::default(),
+                streams: StorageHashMap::default(),
+                streams_length: 0,
+                reserved: StorageHashMap::default(),
+                timelock_delay: 0,
+                timelock_limits: StorageHashMap::default(),
+                queued_withdrawals: StorageHashMap::default(),
+                queued_withdrawals_length: 0,
+                payroll: StorageHashMap::default(),
+                payroll_length: 0,
+                payroll_ids: StorageHashMap::default(),
+                payroll_positions: StorageHashMap::default(),
+                payroll_ids_length: 0,
+                payroll_cursor: 0,
+            }
+        }
+
+
+
+        pub fn get_erc20_by_address(&self, address:AccountId) -> Erc20 {
+            let  erc20_instance: Erc20 = ink_env::call::FromAccountId::from_account_id(address);
+            erc20_instance
+
+        }
+
+        pub fn get_auth_by_address(&self, address:AccountId) -> Auth {
+            let  auth_instance: Auth = ink_env::call::FromAccountId::from_account_id(address);
+            auth_instance
+
+        }
+
+        pub fn get_orgmanager_by_address(&self, address:AccountId) -> OrgManager {
+            let  org_instance: OrgManager = ink_env::call::FromAccountId::from_account_id(address);
+            org_instance
+
+        }
+
+
+        /// add a token to the support list, or list a delisted token again, caching its metadata
+        #[ink(message)]
+        pub fn add_vault_token(&mut self,erc_20_address:AccountId) -> bool  {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("add_vault_token"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            let status = self.tokens.get(&erc_20_address).map(|token| token.status);
+
+            match status {
+                Some(TokenStatus::Visible) | Some(TokenStatus::Hidden) => { false},
+                _ => {
+                    let (name, symbol, decimals) = self.token_metadata(erc_20_address);
+
+                    self.tokens.insert(erc_20_address, TokenInfo{
+                        token_address:erc_20_address,
+                        name:name,
+                        symbol:symbol,
+                        decimals:decimals,
+                        status:TokenStatus::Visible,
+                    });
+
+                    if status.is_none() {
+                        self.token_list.push(erc_20_address);
+                    }
+
+                    self.env().emit_event(AddVaultTokenEvent{
+                        token_address:erc_20_address,
+                        });
+                    true
+                }
+            }
+        }
+
+
+        #[ink(message)]
+        pub fn remove_vault_token(&mut self,erc_20_address: AccountId) -> bool  {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("remove_vault_token"));
+
+            if is_permission == false {
+                return false;
+            }
+
+
+
+            if self.set_token_status(erc_20_address, TokenStatus::Visible, TokenStatus::Hidden) == false {
+                return false;
+            }
+
+            self.env().emit_event(RemoveVaultTokenEvent{
+                token_address:erc_20_address,
+                });
+            true
+        }
+
+
+        /// show a hidden token in the support list again
+        #[ink(message)]
+        pub fn unhide_vault_token(&mut self,erc_20_address: AccountId) -> bool  {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("unhide_vault_token"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            if self.set_token_status(erc_20_address, TokenStatus::Hidden, TokenStatus::Visible) == false {
+                return false;
+            }
+
+            self.env().emit_event(UnhideVaultTokenEvent{
+                token_address:erc_20_address,
+                });
+            true
+        }
+
+
+        /// stop supporting a visible or hidden token, `add_vault_token` lists it again
+        #[ink(message)]
+        pub fn delist_vault_token(&mut self,erc_20_address: AccountId) -> bool  {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("delist_vault_token"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            if self.set_token_status(erc_20_address, TokenStatus::Visible, TokenStatus::Delisted) == false
+                && self.set_token_status(erc_20_address, TokenStatus::Hidden, TokenStatus::Delisted) == false {
+                return false;
+            }
+
+            self.env().emit_event(DelistVaultTokenEvent{
+                token_address:erc_20_address,
+                });
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_token_list(&self) -> ink_prelude::vec::Vec<AccountId> {
+            self.token_list
+                .iter()
+                .filter(|token_address| self.is_token(**token_address, &[TokenStatus::Visible]))
+                .copied()
+                .collect()
+        }
+
+
+        /// get the metadata, status and vault balance of the visible and hidden tokens
+        #[ink(message)]
+        pub fn get_token_details(&self) -> ink_prelude::vec::Vec<TokenDetails> {
+            self.token_list
+                .iter()
+                .map(|token_address| self.tokens.get(token_address).unwrap())
+                .filter(|token| token.status != TokenStatus::Delisted)
+                .map(|token| TokenDetails{
+                    token: token.clone(),
+                    balance: self.token_balance(token.token_address),
+                    reserved: *self.reserved.get(&Some(token.token_address)).unwrap_or(&0),
+                })
+                .collect()
+        }
+
+
+        #[ink(message)]
+        pub fn get_token_info(&self,erc_20_address: AccountId) -> Option<TokenInfo> {
+            self.tokens.get(&erc_20_address).cloned()
+        }
+
+
+
+        #[ink(message)]
+        pub fn get_balance_of(&self,erc_20_address: AccountId) -> u64 {
+
+            if self.tokens.contains_key(&erc_20_address) {
+
+                let balanceof = self.token_balance(erc_20_address);
+
+
+                self.env().emit_event(GetTokenBalanceEvent{
+                    token_address:erc_20_address,
+                    balance:balanceof,});
+
+                balanceof
+
+            } else{
+                0
+            }
+        }
+
+
+        /// deposit tokens the caller approved the vault to transfer
+        #[ink(message)]
+        pub fn deposit(&mut self, erc_20_address:AccountId, value:u64) -> bool {
+
+            let from_address = self.env().caller();
+
+            let to_address = self.vault_contract_address;
+
+            if self.is_token(erc_20_address, &[TokenStatus::Visible, TokenStatus::Hidden]) {
+
+                // let  balanceof =  self.get_balance_of(erc_20_address);
+
+
+                let transfer_result = self.token_transfer_from(erc_20_address, from_address, to_address, value);
+
+                if transfer_result == false {
+                    return false;
+                }
+
+                self.record_deposit(erc_20_address, from_address, value);
+                true
+
+            } else{
+                false
+            }
+        }
+
+
+        /// called by a token's `transfer_and_call` after it transferred the value to the vault,
+        /// the caller has to be a visible or hidden token, returning false reverts the transfer
+        #[ink(message, selector = "0x41EB7788")]
+        pub fn on_token_received(&mut self, from_address:AccountId, value:u64, _data:ink_prelude::vec::Vec<u8>) -> bool {
+
+            let erc_20_address = self.env().caller();
+
+            if !self.is_token(erc_20_address, &[TokenStatus::Visible, TokenStatus::Hidden]) {
+                return false;
+            }
+
+            self.record_deposit(erc_20_address, from_address, value);
+            true
+        }
+
+
+
+        /// withdraw a token, returns whether it was paid, became a withdraw request or was queued
+        #[ink(message)]
+        pub fn withdraw(&mut self,erc_20_address:AccountId,to_address:AccountId,value:u64) -> Option<WithdrawOutcome> {
+
+            if self.is_token(erc_20_address, &[TokenStatus::Visible]) {
+
+
+                let caller = self.env().caller();
+
+                let is_permission = self.has_permission(caller,String::from("vault"),String::from("withdraw"));
+
+                // without the permission the withdrawal is paid from a budget of the caller
+                let budget_key = if is_permission {
+                    None
+                } else {
+                    match self.find_budget(caller, Some(erc_20_address), value as Balance) {
+                        Some(budget_key) => Some(budget_key),
+                        None => return None,
+                    }
+                };
+
+                // withdrawals from a budget are spent from it when requested or queued
+                if self.needs_confirmations(Some(erc_20_address), value as Balance) {
+                    self.spend_budget(&budget_key, value as Balance);
+                    return Some(WithdrawOutcome::Requested(self.submit_withdraw_request(Some(erc_20_address), to_address, value as Balance, WithdrawalKind::Transfer)));
+                }
+
+                if self.needs_timelock(Some(erc_20_address), value as Balance) {
+                    self.spend_budget(&budget_key, value as Balance);
+                    self.count_unconfirmed(Some(erc_20_address), value as Balance);
+                    return Some(WithdrawOutcome::Queued(self.queue_withdrawal(Some(erc_20_address), to_address, value as Balance, WithdrawalKind::Transfer)));
+                }
+
+                if self.unreserved_balance(Some(erc_20_address)) < value as Balance {
+                    return None;
+                }
+
+                if self.transfer_token_out(erc_20_address, to_address, value) == false {
+                    return None;
+                }
+
+                self.spend_budget(&budget_key, value as Balance);
+                self.count_unconfirmed(Some(erc_20_address), value as Balance);
+
+                Some(WithdrawOutcome::Done(self.transfer_count))
+
+            } else{
+                None
+            }
+        }
+
+
+
+        /// deposit the native currency transferred with the call
+        #[ink(message, payable)]
+        pub fn deposit_native(&mut self) -> bool {
+
+            let from_address = self.env().caller();
+            let value = self.env().transferred_balance();
+
+            if value == 0 {
+                return false;
+            }
+
+            self.record_transfer(Direction::In, None, String::from(NATIVE_TOKEN_NAME), from_address, self.vault_contract_address, value);
+
+            self.env().emit_event(DepositNativeEvent{
+                from_address:from_address,
+                value:value,});
+
+            true
+        }
+
+
+        /// withdraw native currency, with the same permission and outcomes as `withdraw`
+        #[ink(message)]
+        pub fn withdraw_native(&mut self,to_address:AccountId,value:Balance) -> Option<WithdrawOutcome> {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("withdraw"));
+
+            // without the permission the withdrawal is paid from a budget of the caller
+            let budget_key = if is_permission {
+                None
+            } else {
+                match self.find_budget(caller, None, value) {
+                    Some(budget_key) => Some(budget_key),
+                    None => return None,
+                }
+            };
+
+            // withdrawals from a budget are spent from it when requested or queued
+            if self.needs_confirmations(None, value) {
+                self.spend_budget(&budget_key, value);
+                return Some(WithdrawOutcome::Requested(self.submit_withdraw_request(None, to_address, value, WithdrawalKind::Transfer)));
+            }
+
+            if self.needs_timelock(None, value) {
+                self.spend_budget(&budget_key, value);
+                self.count_unconfirmed(None, value);
+                return Some(WithdrawOutcome::Queued(self.queue_withdrawal(None, to_address, value, WithdrawalKind::Transfer)));
+            }
+
+            if self.unreserved_balance(None) < value {
+                return None;
+            }
+
+            if self.transfer_native_out(to_address, value) == false {
+                return None;
+            }
+
+            self.spend_budget(&budget_key, value);
+            self.count_unconfirmed(None, value);
+
+            Some(WithdrawOutcome::Done(self.transfer_count))
+        }
+
+
+        /// set the budget of a holder for a token, none for the native currency, replacing the
+        /// previous one, its first period starts now
+        #[ink(message)]
+        pub fn set_budget(&mut self,holder:BudgetHolder,token_address:Option<AccountId>,amount:Balance,period:u64) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_budget"));
+
+            if is_permission == false || period == 0 {
+                return false;
+            }
+
+            let now = self.env().block_timestamp();
+            let key = (holder.clone(), token_address);
+            if !self.budgets.contains_key(&key) {
+                if let BudgetHolder::Role { .. } = holder {
+                    let roles = self.role_budgets.entry(token_address).or_insert(ink_prelude::vec::Vec::new());
+                    if roles.len() >= MAX_ROLE_BUDGETS {
+                        return false;
+                    }
+                    roles.push(holder.clone());
+                }
+                self.budget_keys.insert(self.budgets_length, key.clone());
+                self.budget_positions.insert(key.clone(), self.budgets_length);
+                self.budgets_length += 1;
+            }
+            self.budgets.insert(key, Budget{
+                holder:holder,
+                token_address:token_address,
+                amount:amount,
+                period:period,
+                start_time:now,
+                period_start:now,
+                spent:0,
+            });
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn remove_budget(&mut self,holder:BudgetHolder,token_address:Option<AccountId>) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_budget"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            let key = (holder, token_address);
+            if self.budgets.take(&key).is_none() {
+                return false;
+            }
+            if let Some(roles) = self.role_budgets.get_mut(&token_address) {
+                roles.retain(|role| *role != key.0);
+            }
+            let position = self.budget_positions.take(&key).unwrap();
+            self.budgets_length -= 1;
+            let last_key = self.budget_keys.take(&self.budgets_length).unwrap();
+            if position != self.budgets_length {
+                self.budget_keys.insert(position, last_key.clone());
+                self.budget_positions.insert(last_key, position);
+            }
+            true
+        }
+
+
+        /// get a budget with its spending in the current period
+        #[ink(message)]
+        pub fn get_budget(&self,holder:BudgetHolder,token_address:Option<AccountId>) -> Option<Budget> {
+            self.budgets.get(&(holder, token_address)).map(|budget| self.current_budget(budget))
+        }
+
+
+        /// get all budgets with their spending in the current period
+        #[ink(message)]
+        pub fn get_budgets(&self) -> ink_prelude::vec::Vec<Budget> {
+            (0..self.budgets_length)
+                .map(|position| self.current_budget(self.budgets.get(self.budget_keys.get(&position).unwrap()).unwrap()))
+                .collect()
+        }
+
+
+        /// set the threshold above which withdrawals of a token, none for the native currency,
+        /// become requests confirmed by moderators, none to remove it. The withdrawals which were
+        /// not confirmed add up against the threshold in every threshold period
+        #[ink(message)]
+        pub fn set_withdraw_threshold(&mut self,token_address:Option<AccountId>,threshold:Option<Balance>) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_withdraw_threshold"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            match threshold {
+                Some(threshold) => { self.withdraw_thresholds.insert(token_address, threshold); }
+                None => { self.withdraw_thresholds.take(&token_address); }
+            }
+            true
+        }
+
+
+        /// set how many moderators besides the submitter have to confirm a withdraw request, no
+        /// more than there are moderators, how long a request can be confirmed and executed, 0 if
+        /// it doesn't expire, and the length of the periods withdrawals add up in against thresholds
+        #[ink(message)]
+        pub fn set_withdraw_request_rules(&mut self,required_confirmations:u32,request_lifetime:u64,threshold_period:u64) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_withdraw_request_rules"));
+
+            if is_permission == false || required_confirmations == 0 || threshold_period == 0 {
+                return false;
+            }
+
+            if required_confirmations as usize > self.moderator_list().len() {
+                return false;
+            }
+
+            self.required_confirmations = required_confirmations;
+            self.request_lifetime = request_lifetime;
+            self.threshold_period = threshold_period;
+            true
+        }
+
+
+        /// confirm a withdraw request, only by a moderator of the org other than its submitter
+        #[ink(message)]
+        pub fn confirm_withdraw_request(&mut self,request_id:RequestId) -> Option<ConfirmationStatus> {
+
+            let moderator = self.env().caller();
+
+            if !self.is_request_open(request_id) || !self.is_moderator(moderator) {
+                return None;
+            }
+
+            if self.withdraw_requests.get(&request_id).unwrap().submitter == moderator {
+                return None;
+            }
+
+            Some(self.confirm_by_caller(moderator, request_id))
+        }
+
+
+        /// revoke the caller's confirmation of a withdraw request
+        #[ink(message)]
+        pub fn revoke_withdraw_confirmation(&mut self,request_id:RequestId) -> bool {
+
+            let moderator = self.env().caller();
+
+            if !self.is_request_open(request_id) || self.request_confirmations.take(&(request_id, moderator)).is_none() {
+                return false;
+            }
+
+            self.env().emit_event(RevokeWithdrawRequestEvent{
+                request_id:request_id,
+                moderator:moderator,});
+
+            true
+        }
+
+
+        /// run a confirmed withdraw request, by anyone, it is queued if the withdrawal is timelocked
+        #[ink(message)]
+        pub fn execute_withdraw_request(&mut self,request_id:RequestId) -> bool {
+
+            if !self.is_request_open(request_id) || self.confirmation_status(request_id) != ConfirmationStatus::Confirmed {
+                return false;
+            }
+
+            let request = self.withdraw_requests.get(&request_id).unwrap().clone();
+
+            let transfer_result = if self.needs_timelock(request.token_address, request.value) {
+                self.queue_withdrawal(request.token_address, request.to_address, request.value, request.kind.clone());
+                true
+            } else {
+                self.run_withdrawal(&request.kind, request.token_address, request.to_address, request.value)
+            };
+
+            if transfer_result == false {
+                return false;
+            }
+
+            self.withdraw_requests.get_mut(&request_id).unwrap().executed = true;
+
+            self.env().emit_event(ExecuteWithdrawRequestEvent{
+                request_id:request_id,});
+
+            true
+        }
+
+
+        /// create a stream paying a token, none for the native currency, to the recipient, the
+        /// total is reserved in the vault until it is claimed or the stream is cancelled. Like a
+        /// withdrawal, a total above the threshold becomes a withdraw request and a timelocked
+        /// one is queued, the stream is created when they run
+        #[ink(message)]
+        pub fn create_stream(&mut self,recipient:AccountId,token_address:Option<AccountId>,total:Balance,start_time:u64,end_time:u64,cliff_time:Option<u64>) -> Option<WithdrawOutcome> {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("create_stream"));
+
+            if is_permission == false || total == 0 || start_time >= end_time {
+                return None;
+            }
+
+            // a stream starting in the past could be claimed at once
+            if start_time < self.env().block_timestamp() {
+                return None;
+            }
+
+            if cliff_time.map_or(false, |cliff_time| cliff_time < start_time || cliff_time > end_time) {
+                return None;
+            }
+
+            if token_address.map_or(false, |erc_20_address| !self.is_token(erc_20_address, &[TokenStatus::Visible])) {
+                return None;
+            }
+
+            if self.unreserved_balance(token_address) < total {
+                return None;
+            }
+
+            let kind = WithdrawalKind::Stream{
+                start_time:start_time,
+                end_time:end_time,
+                cliff_time:cliff_time,
+            };
+
+            if self.needs_confirmations(token_address, total) {
+                return Some(WithdrawOutcome::Requested(self.submit_withdraw_request(token_address, recipient, total, kind)));
+            }
+
+            self.count_unconfirmed(token_address, total);
+
+            if self.needs_timelock(token_address, total) {
+                return Some(WithdrawOutcome::Queued(self.queue_withdrawal(token_address, recipient, total, kind)));
+            }
+
+            Some(WithdrawOutcome::Done(self.start_stream(recipient, token_address, total, start_time, end_time, cliff_time)))
+        }
+
+
+        /// pay the recipient of a stream what has vested and is not claimed yet
+        #[ink(message)]
+        pub fn claim_stream(&mut self,stream_id:StreamId) -> bool {
+
+            let stream = match self.streams.get(&stream_id) {
+                Some(stream) => stream.clone(),
+                None => return false,
+            };
+
+            if self.env().caller() != stream.recipient {
+                return false;
+            }
+
+            let value = self.claimable(&stream);
+
+            if value == 0 || self.pay_stream(&stream, value) == false {
+                return false;
+            }
+
+            self.env().emit_event(ClaimStreamEvent{
+                stream_id:stream_id,
+                value:value,});
+
+            true
+        }
+
+
+        /// cancel a stream, what has vested is paid to the recipient and the rest is released to the vault
+        #[ink(message)]
+        pub fn cancel_stream(&mut self,stream_id:StreamId) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("cancel_stream"));
+
+            let stream = match self.streams.get(&stream_id) {
+                Some(stream) => stream.clone(),
+                None => return false,
+            };
+
+            if is_permission == false || stream.cancelled {
+                return false;
+            }
+
+            let value = self.claimable(&stream);
+
+            if value > 0 && self.pay_stream(&stream, value) == false {
+                return false;
+            }
+
+            let remainder = stream.total - stream.claimed - value;
+            *self.reserved.get_mut(&stream.token_address).unwrap() -= remainder;
+            self.streams.get_mut(&stream_id).unwrap().cancelled = true;
+
+            if remainder > 0 {
+                let token_name = self.token_name(stream.token_address);
+                self.record_transfer(Direction::Release, stream.token_address, token_name, self.vault_contract_address, stream.recipient, remainder);
+            }
+
+            self.env().emit_event(CancelStreamEvent{
+                stream_id:stream_id,
+                remainder:remainder,});
+
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_stream(&self,stream_id:StreamId) -> Option<Stream> {
+            self.streams.get(&stream_id).cloned()
+        }
+
+
+        /// what the recipient of a stream can claim now
+        #[ink(message)]
+        pub fn get_stream_claimable(&self,stream_id:StreamId) -> Balance {
+            self.streams.get(&stream_id).map_or(0, |stream| self.claimable(stream))
+        }
+
+
+        /// record the difference between the balance of a token, none for the native currency, the
+        /// vault holds and the one its transfer history accounts for, false if there is none
+        #[ink(message)]
+        pub fn reconcile(&mut self,token_address:Option<AccountId>) -> bool {
+
+            if token_address.map_or(false, |erc_20_address| !self.tokens.contains_key(&erc_20_address)) {
+                return false;
+            }
+
+            let actual = self.actual_balance(token_address);
+            let recorded = *self.recorded_balances.get(&token_address).unwrap_or(&0);
+
+            let (transfer_direction, value) = if actual > recorded {
+                (Direction::ExternalIn, actual - recorded)
+            } else if actual < recorded {
+                (Direction::ExternalOut, recorded - actual)
+            } else {
+                return false;
+            };
+
+            let token_name = self.token_name(token_address);
+            self.record_transfer(transfer_direction, token_address, token_name, self.vault_contract_address, self.vault_contract_address, value);
+
+            self.env().emit_event(ReconcileEvent{
+                token_address:token_address,
+                transfer_direction:transfer_direction,
+                value:value,});
+
+            true
+        }
+
+
+        /// get the recorded and actual balances of the native currency and of every registered token
+        #[ink(message)]
+        pub fn get_discrepancies(&self) -> ink_prelude::vec::Vec<Discrepancy> {
+            core::iter::once(None)
+                .chain(self.token_list.iter().map(|token_address| Some(*token_address)))
+                .map(|token_address| Discrepancy{
+                    token_address: token_address,
+                    recorded: *self.recorded_balances.get(&token_address).unwrap_or(&0),
+                    actual: self.actual_balance(token_address),
+                })
+                .collect()
+        }
+
+
+        /// add a payment of the amount of a token, none for the native currency, to the recipient
+        /// every interval, the first one is due at the first due time. Like a withdrawal, an
+        /// amount above the threshold becomes a withdraw request and a timelocked one is queued,
+        /// the entry is added when they run
+        #[ink(message)]
+        pub fn add_payroll(&mut self,recipient:AccountId,token_address:Option<AccountId>,amount:Balance,interval:u64,first_due:u64) -> Option<WithdrawOutcome> {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_payroll"));
+
+            if is_permission == false || amount == 0 || interval == 0 {
+                return None;
+            }
+
+            // a payment due in the past would be paid at once
+            if first_due < self.env().block_timestamp() {
+                return None;
+            }
+
+            if token_address.map_or(false, |erc_20_address| !self.is_token(erc_20_address, &[TokenStatus::Visible])) {
+                return None;
+            }
+
+            let kind = WithdrawalKind::Payroll{
+                interval:interval,
+                first_due:first_due,
+            };
+
+            if self.needs_confirmations(token_address, amount) {
+                return Some(WithdrawOutcome::Requested(self.submit_withdraw_request(token_address, recipient, amount, kind)));
+            }
+
+            self.count_unconfirmed(token_address, amount);
+
+            if self.needs_timelock(token_address, amount) {
+                return Some(WithdrawOutcome::Queued(self.queue_withdrawal(token_address, recipient, amount, kind)));
+            }
+
+            Some(WithdrawOutcome::Done(self.start_payroll(recipient, token_address, amount, interval, first_due)))
+        }
+
+
+        /// stop paying a payroll entry, payments which are due and not processed yet are dropped
+        #[ink(message)]
+        pub fn remove_payroll(&mut self,payroll_id:PayrollId) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_payroll"));
+
+            if is_permission == false || self.payroll.take(&payroll_id).is_none() {
+                return false;
+            }
+
+            let position = self.payroll_positions.take(&payroll_id).unwrap();
+            self.payroll_ids_length -= 1;
+            let last_id = self.payroll_ids.take(&self.payroll_ids_length).unwrap();
+            if position != self.payroll_ids_length {
+                self.payroll_ids.insert(position, last_id);
+                self.payroll_positions.insert(last_id, position);
+            }
+
+            self.env().emit_event(RemovePayrollEvent{
+                payroll_id:payroll_id,});
+
+            true
+        }
+
+
+        /// pay the payroll entries which are due, looking at no more than `max` entries, due or
+        /// not, and return how many were paid. Entries the vault can't pay are skipped and stay due.
+        /// Each call goes on from the entry the last one stopped at, so every entry is reached.
+        #[ink(message)]
+        pub fn process_due_payments(&mut self,max:u32) -> u32 {
+
+            let now = self.env().block_timestamp();
+            let length = self.payroll_ids_length;
+            let mut position = if self.payroll_cursor < length { self.payroll_cursor } else { 0 };
+            let mut paid = 0;
+
+            for _ in 0..length.min(max) {
+                let payroll_id = *self.payroll_ids.get(&position).unwrap();
+                position = (position + 1) % length;
+
+                let entry = self.payroll.get(&payroll_id).unwrap().clone();
+                if entry.next_due > now {
+                    continue;
+                }
+
+                if self.unreserved_balance(entry.token_address) < entry.amount
+                    || self.pay_out(entry.token_address, entry.recipient, entry.amount) == false {
+                    self.env().emit_event(PayrollSkippedEvent{
+                        payroll_id:payroll_id,
+                        value:entry.amount,
+                        due_time:entry.next_due,});
+                    continue;
+                }
+
+                self.payroll.get_mut(&payroll_id).unwrap().next_due += entry.interval;
+                paid += 1;
+
+                self.env().emit_event(PayrollPaidEvent{
+                    payroll_id:payroll_id,
+                    value:entry.amount,
+                    due_time:entry.next_due,});
+            }
+
+            self.payroll_cursor = position;
+            paid
+        }
+
+
+        #[ink(message)]
+        pub fn get_payroll(&self,payroll_id:PayrollId) -> Option<PayrollEntry> {
+            self.payroll.get(&payroll_id).cloned()
+        }
+
+
+        /// get the payroll entries, the last one takes the place of an entry removed
+        #[ink(message)]
+        pub fn get_payrolls(&self) -> ink_prelude::vec::Vec<PayrollEntry> {
+            (0..self.payroll_ids_length)
+                .map(|position| self.payroll.get(self.payroll_ids.get(&position).unwrap()).unwrap().clone())
+                .collect()
+        }
+
+
+        /// set how long withdrawals wait in the queue, 0 to stop queueing them
+        #[ink(message)]
+        pub fn set_timelock_delay(&mut self,delay:u64) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_timelock"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            self.timelock_delay = delay;
+            true
+        }
+
+
+        /// queue only the withdrawals of a token, none for the native currency, above the limit,
+        /// none to queue all of them
+        #[ink(message)]
+        pub fn set_timelock_limit(&mut self,token_address:Option<AccountId>,limit:Option<Balance>) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("set_timelock"));
+
+            if is_permission == false {
+                return false;
+            }
+
+            match limit {
+                Some(limit) => { self.timelock_limits.insert(token_address, limit); }
+                None => { self.timelock_limits.take(&token_address); }
+            }
+            true
+        }
+
+
+        /// cancel a queued withdrawal before its eta
+        #[ink(message)]
+        pub fn cancel_queued_withdrawal(&mut self,queue_id:QueueId) -> bool {
+
+            let caller = self.env().caller();
+
+            let is_permission = self.has_permission(caller,String::from("vault"),String::from("cancel_queued_withdrawal"));
+
+            let now = self.env().block_timestamp();
+
+            match self.queued_withdrawals.get_mut(&queue_id) {
+                Some(queued) if is_permission && queued.state == QueuedWithdrawalState::Queued && now < queued.eta => {
+                    queued.state = QueuedWithdrawalState::Cancelled;
+                }
+                _ => return false,
+            }
+
+            self.env().emit_event(CancelQueuedWithdrawalEvent{
+                queue_id:queue_id,});
+
+            true
+        }
+
+
+        /// execute a queued withdrawal from its eta on, by anyone
+        #[ink(message)]
+        pub fn execute_queued_withdrawal(&mut self,queue_id:QueueId) -> bool {
+
+            let queued = match self.queued_withdrawals.get(&queue_id) {
+                Some(queued) => queued.clone(),
+                None => return false,
+            };
+
+            if queued.state != QueuedWithdrawalState::Queued || self.env().block_timestamp() < queued.eta {
+                return false;
+            }
+
+            if self.run_withdrawal(&queued.kind, queued.token_address, queued.to_address, queued.value) == false {
+                return false;
+            }
+
+            self.queued_withdrawals.get_mut(&queue_id).unwrap().state = QueuedWithdrawalState::Executed;
+
+            self.env().emit_event(ExecuteQueuedWithdrawalEvent{
+                queue_id:queue_id,});
+
+            true
+        }
+
+
+        #[ink(message)]
+        pub fn get_queued_withdrawal(&self,queue_id:QueueId) -> Option<QueuedWithdrawal> {
+            self.queued_withdrawals.get(&queue_id).cloned()
+        }
+
+
+        #[ink(message)]
+        pub fn get_timelock_delay(&self) -> u64 {
+            self.timelock_delay
+        }
+
+
+        #[ink(message)]
+        pub fn get_withdraw_request(&self,request_id:RequestId) -> Option<WithdrawRequest> {
+            self.withdraw_requests.get(&request_id).cloned()
+        }
+
+
+        /// moderators of the org who confirmed a withdraw request
+        #[ink(message)]
+        pub fn get_withdraw_request_confirmations(&self,request_id:RequestId) -> ink_prelude::vec::Vec<AccountId> {
+            self.moderator_list()
+                .into_iter()
+                .filter(|moderator| self.request_confirmations.contains_key(&(request_id, *moderator)))
+                .collect()
+        }
+
+
+        /// native currency balance of the vault
+        #[ink(message)]
+        pub fn get_native_balance(&self) -> Balance {
+            self.env().balance()
+        }
+
+
+        #[ink(message)]
+        pub fn get_transfer_history(&self) -> ink_prelude::vec::Vec<Transfer> {
+            (1..=self.transfer_count)
+                .map(|transfer_id| self.transfer_history.get(&transfer_id).unwrap().clone())
+                .collect()
+        }
+
+
+        /// get a page of the transfers matching the filter, ordered by id from the cursor on
+        ///
+        /// the page holds at most `limit` transfers, capped to MAX_PAGE_SIZE, and is cut short
+        /// after looking at MAX_PAGE_SCAN transfers; query the next page from `next_cursor`
+        #[ink(message)]
+        pub fn get_transfers(&self,filter:TransferFilter,cursor:Option<TransferId>,limit:u32) -> TransferPage {
+            let limit = limit.min(MAX_PAGE_SIZE) as usize;
+            // transfer times never decrease with the ids, so the time window is a range of ids
+            let mut from_id = cursor.unwrap_or(1).max(1);
+            if let Some(from_time) = filter.from_time {
+                from_id = from_id.max(self.first_transfer_at(from_time));
+            }
+            let end_id = filter.to_time.map_or(self.transfer_count + 1, |to_time| self.first_transfer_at(to_time));
+            // walk the smallest index holding all the matching transfers, or all the ids
+            let index = ink_prelude::vec![
+                filter.token_address.map(TransferIndex::Token),
+                filter.counterparty.map(TransferIndex::Counterparty),
+                filter.direction.map(TransferIndex::Direction),
+            ]
+                .into_iter()
+                .flatten()
+                .min_by_key(|index| self.index_length(index));
+            let (mut position, end_position) = match &index {
+                Some(index) => (self.index_position(index, from_id), self.index_length(index)),
+                None => (from_id, end_id),
+            };
+            let mut transfers = ink_prelude::vec::Vec::new();
+            let mut next_cursor = None;
+            let mut scanned = 0;
+            while position < end_position {
+                let transfer_id = match &index {
+                    Some(index) => *self.transfer_index.get(&(index.clone(), position)).unwrap(),
+                    None => position,
+                };
+                if transfer_id >= end_id {
+                    break;
+                }
+                if transfers.len() == limit || scanned == MAX_PAGE_SCAN {
+                    next_cursor = Some(transfer_id);
+                    break;
+                }
+                scanned += 1;
+                position += 1;
+                let transfer = self.transfer_history.get(&transfer_id).unwrap();
+                if self.transfer_matches(transfer, &filter) {
+                    transfers.push(transfer.clone());
+                }
+            }
+            TransferPage {
+                transfers,
+                next_cursor,
+            }
+        }
+
+
+        fn transfer_token_out(&mut self, erc_20_address: AccountId, to_address: AccountId, value: u64) -> bool {
+
+            let from_address = self.vault_contract_address;
+
+            let token_name = self.token_name(Some(erc_20_address));
+
+            let transfer_result  = self.token_transfer(erc_20_address, to_address, value);
+
+            if transfer_result == false {
+                return false;
+            }
+
+            self.record_transfer(Direction::Out, Some(erc_20_address), token_name.clone(), from_address, to_address, value as Balance);
+
+            self.env().emit_event(WithdrawTokenEvent{
+                token_name: token_name.clone(),
+                to_address:to_address,
+                value:value,});
+
+            true
+        }
+
+
+        fn record_deposit(&mut self, erc_20_address: AccountId, from_address: AccountId, value: u64) {
+
+            let token_name = self.token_name(Some(erc_20_address));
+
+            self.record_transfer(Direction::In, Some(erc_20_address), token_name.clone(), from_address, self.vault_contract_address, value as Balance);
+
+            self.env().emit_event(DepositTokenEvent{
+                token_name: token_name.clone(),
+                from_address:from_address,
+                value:value});
+        }
+
+
+        fn transfer_native_out(&mut self, to_address: AccountId, value: Balance) -> bool {
+
+            // fails if the vault can't keep its existential deposit
+            if self.env().transfer(to_address, value).is_err() {
+                return false;
+            }
+
+            self.record_transfer(Direction::Out, None, String::from(NATIVE_TOKEN_NAME), self.vault_contract_address, to_address, value);
+
+            self.env().emit_event(WithdrawNativeEvent{
+                to_address:to_address,
+                value:value,});
+
+            true
+        }
+
+
+        fn claimable(&self, stream: &Stream) -> Balance {
+            if stream.cancelled {
+                return 0;
+            }
+            vested_amount(stream.total, stream.start_time, stream.end_time, stream.cliff_time, self.env().block_timestamp()) - stream.claimed
+        }
+
+
+        /// pay a stream's recipient out of the stream's reserve
+        fn pay_stream(&mut self, stream: &Stream, value: Balance) -> bool {
+            if self.pay_out(stream.token_address, stream.recipient, value) == false {
+                return false;
+            }
+            self.streams.get_mut(&stream.stream_id).unwrap().claimed += value;
+            *self.reserved.get_mut(&stream.token_address).unwrap() -= value;
+            true
+        }
+
+
+        /// balance of a token, none for the native currency, held by the vault
+        fn actual_balance(&self, token_address: Option<AccountId>) -> Balance {
+            match token_address {
+                Some(erc_20_address) => self.token_balance(erc_20_address) as Balance,
+                None => self.env().balance(),
+            }
+        }
+
+
+        /// balance of a token, none for the native currency, which is not reserved for streams
+        fn unreserved_balance(&self, token_address: Option<AccountId>) -> Balance {
+            self.actual_balance(token_address).saturating_sub(*self.reserved.get(&token_address).unwrap_or(&0))
+        }
+
+
+        /// the cached name of a registered token
+        fn token_name(&self, token_address: Option<AccountId>) -> String {
+            match token_address {
+                Some(erc_20_address) => match self.tokens.get(&erc_20_address) {
+                    Some(token) => token.name.clone(),
+                    None => self.token_metadata(erc_20_address).0,
+                },
+                None => String::from(NATIVE_TOKEN_NAME),
+            }
+        }
+
+
+        fn is_token(&self, erc_20_address: AccountId, statuses: &[TokenStatus]) -> bool {
+            self.tokens.get(&erc_20_address).map_or(false, |token| statuses.contains(&token.status))
+        }
+
+
+        /// move a token from one status to another, false if it is not in the `from` status
+        fn set_token_status(&mut self, erc_20_address: AccountId, from: TokenStatus, to: TokenStatus) -> bool {
+            match self.tokens.get_mut(&erc_20_address) {
+                Some(token) if token.status == from => {
+                    token.status = to;
+                    true
+                }
+                _ => false,
+            }
+        }
+
+
+        /// the budget as of the current period
+        fn current_budget(&self, budget: &Budget) -> Budget {
+            let mut budget = budget.clone();
+            let period_start = current_period_start(budget.start_time, budget.period, self.env().block_timestamp());
+            if period_start != budget.period_start {
+                budget.period_start = period_start;
+                budget.spent = 0;
+            }
+            budget
+        }
+
+
+        /// a budget of the account for the token with the value left, its own budget first
+        /// then those of its roles for the token
+        fn find_budget(&self, account: AccountId, token_address: Option<AccountId>, value: Balance) -> Option<(BudgetHolder, Option<AccountId>)> {
+            let covers = |key: &(BudgetHolder, Option<AccountId>)| {
+                self.budgets.get(key).map_or(false, |budget| {
+                    let budget = self.current_budget(budget);
+                    budget.amount - budget.spent >= value
+                })
+            };
+            let own_key = (BudgetHolder::Account(account), token_address);
+            if covers(&own_key) {
+                return Some(own_key);
+            }
+            self.role_budgets.get(&token_address)?
+                .iter()
+                .map(|holder| (holder.clone(), token_address))
+                .find(|key| match key {
+                    (BudgetHolder::Role { contract_name, function_name }, _) => {
+                        covers(key) && self.has_permission(account, contract_name.clone(), function_name.clone())
+                    }
+                    _ => false,
+                })
+        }
+
+
+        /// spend the value from the budget, if the withdrawal is paid from one
+        fn spend_budget(&mut self, key: &Option<(BudgetHolder, Option<AccountId>)>, value: Balance) {
+            let key = match key {
+                Some(key) => key,
+                None => return,
+            };
+            let mut budget = self.current_budget(self.budgets.get(key).unwrap());
+            budget.spent += value;
+            self.budgets.insert(key.clone(), budget);
+        }
+
 
+        /// transfer a token, none for the native currency, out of the vault
+        fn pay_out(&mut self, token_address: Option<AccountId>, to_address: AccountId, value: Balance) -> bool {
+            match token_address {
+                Some(erc_20_address) => self.transfer_token_out(erc_20_address, to_address, value as u64),
+                None => self.transfer_native_out(to_address, value),
+            }
+        }
 
 
+        /// run a confirmed or queued withdrawal, false if the unreserved balance is too low or the transfer fails
+        fn run_withdrawal(&mut self, kind: &WithdrawalKind, token_address: Option<AccountId>, to_address: AccountId, value: Balance) -> bool {
+            if let WithdrawalKind::Payroll { interval, first_due } = kind {
+                // nothing is paid until the entry is due
+                let first_due = (*first_due).max(self.env().block_timestamp());
+                self.start_payroll(to_address, token_address, value, *interval, first_due);
+                return true;
+            }
+            if self.unreserved_balance(token_address) < value {
+                return false;
+            }
+            match kind {
+                WithdrawalKind::Stream { start_time, end_time, cliff_time } => {
+                    self.start_stream(to_address, token_address, value, *start_time, *end_time, *cliff_time);
+                    true
+                }
+                _ => self.pay_out(token_address, to_address, value),
+            }
+        }
 
-    impl VaultManager {
 
-        #[ink(constructor)]
-        pub fn new(org_contract_address: AccountId,auth_contract_address: AccountId) -> Self {
+        /// store a payroll entry
+        fn start_payroll(&mut self, recipient: AccountId, token_address: Option<AccountId>, amount: Balance, interval: u64, first_due: u64) -> PayrollId {
 
-            let vault_contract_address = Self::env().account_id();
+            let payroll_id = self.payroll_length;
+            self.payroll_length += 1;
 
-            Self {
-                org_contract_address:org_contract_address,
-                auth_contract_address:auth_contract_address,
-                tokens: StorageHashMap::default(),
-                visible_tokens: StorageHashMap::default(),
-                transfer_history: StorageHashMap::default(),
-                vault_contract_address: vault_contract_address,
+            self.payroll.insert(payroll_id, PayrollEntry{
+                payroll_id:payroll_id,
+                recipient:recipient,
+                token_address:token_address,
+                amount:amount,
+                interval:interval,
+                next_due:first_due,
+            });
+            self.payroll_ids.insert(self.payroll_ids_length, payroll_id);
+            self.payroll_positions.insert(payroll_id, self.payroll_ids_length);
+            self.payroll_ids_length += 1;
 
+            self.env().emit_event(AddPayrollEvent{
+                payroll_id:payroll_id,
+                recipient:recipient,});
 
-            }
+            payroll_id
         }
 
 
+        /// store a stream and reserve its total
+        fn start_stream(&mut self, recipient: AccountId, token_address: Option<AccountId>, total: Balance, start_time: u64, end_time: u64, cliff_time: Option<u64>) -> StreamId {
 
-        pub fn get_erc20_by_address(&self, address:AccountId) -> Erc20 {
-            let  erc20_instance: Erc20 = ink_env::call::FromAccountId::from_account_id(address);
-            erc20_instance
-
-        }
+            let stream_id = self.streams_length;
+            self.streams_length += 1;
 
-        pub fn get_auth_by_address(&self, address:AccountId) -> Auth {
-            let  auth_instance: Auth = ink_env::call::FromAccountId::from_account_id(address);
-            auth_instance
+            self.streams.insert(stream_id, Stream{
+                stream_id:stream_id,
+                recipient:recipient,
+                token_address:token_address,
+                total:total,
+                start_time:start_time,
+                end_time:end_time,
+                cliff_time:cliff_time,
+                claimed:0,
+                cancelled:false,
+            });
+            *self.reserved.entry(token_address).or_insert(0) += total;
 
-        }
+            let token_name = self.token_name(token_address);
+            self.record_transfer(Direction::Reserve, token_address, token_name, self.vault_contract_address, recipient, total);
 
-        pub fn get_orgmanager_by_address(&self, address:AccountId) -> OrgManager {
-            let  org_instance: OrgManager = ink_env::call::FromAccountId::from_account_id(address);
-            org_instance
+            self.env().emit_event(CreateStreamEvent{
+                stream_id:stream_id,
+                recipient:recipient,});
 
+            stream_id
         }
 
 
-        #[ink(message)]
-        pub fn add_vault_token(&mut self,erc_20_address:AccountId) -> bool  {
+        fn needs_timelock(&self, token_address: Option<AccountId>, value: Balance) -> bool {
+            self.timelock_delay > 0 && self.timelock_limits.get(&token_address).map_or(true, |limit| value > *limit)
+        }
 
-            let caller = self.env().caller();
 
-            let  auth = self.get_auth_by_address(self.auth_contract_address);
-            
-            let is_permission = auth.has_permission(caller,String::from("vault"),String::from("add_vault_token"));
+        fn queue_withdrawal(&mut self, token_address: Option<AccountId>, to_address: AccountId, value: Balance, kind: WithdrawalKind) -> QueueId {
+
+            let queue_time = self.env().block_timestamp();
+            let eta = queue_time.saturating_add(self.timelock_delay);
+            let queue_id = self.queued_withdrawals_length;
+            self.queued_withdrawals_length += 1;
+
+            self.queued_withdrawals.insert(queue_id, QueuedWithdrawal{
+                queue_id:queue_id,
+                token_address:token_address,
+                to_address:to_address,
+                value:value,
+                kind:kind,
+                queued_by:self.env().caller(),
+                queue_time:queue_time,
+                eta:eta,
+                state:QueuedWithdrawalState::Queued,
+            });
+
+            self.env().emit_event(QueueWithdrawalEvent{
+                queue_id:queue_id,
+                to_address:to_address,
+                value:value,
+                eta:eta,});
+
+            queue_id
+        }
 
-            if is_permission == false {
-                return false;
-            }
 
-            match self.tokens.insert(
-                                     erc_20_address,self.vault_contract_address
-            ) {
+        /// whether the value, added to what was withdrawn without confirmations in the current
+        /// threshold period, is above the threshold of the token
+        fn needs_confirmations(&self, token_address: Option<AccountId>, value: Balance) -> bool {
+            self.withdraw_thresholds.get(&token_address).map_or(false, |threshold| {
+                self.unconfirmed_withdrawn(token_address).saturating_add(value) > *threshold
+            })
+        }
 
-                Some(_) => { false},
-                None => {
-                    self.visible_tokens.insert(
-                                               erc_20_address,self.vault_contract_address);
 
+        /// what was withdrawn of the token without confirmations in the current threshold period
+        fn unconfirmed_withdrawn(&self, token_address: Option<AccountId>) -> Balance {
+            let period_start = current_period_start(0, self.threshold_period, self.env().block_timestamp());
+            match self.unconfirmed_withdrawals.get(&token_address) {
+                Some((start, withdrawn)) if *start == period_start => *withdrawn,
+                _ => 0,
+            }
+        }
 
 
-                    self.env().emit_event(AddVaultTokenEvent{
-                        token_address:erc_20_address,
-                        });
-                    true
-                }
+        /// add a withdrawal without confirmations to the current threshold period of its token
+        fn count_unconfirmed(&mut self, token_address: Option<AccountId>, value: Balance) {
+            if !self.withdraw_thresholds.contains_key(&token_address) {
+                return;
             }
+            let period_start = current_period_start(0, self.threshold_period, self.env().block_timestamp());
+            let withdrawn = self.unconfirmed_withdrawn(token_address).saturating_add(value);
+            self.unconfirmed_withdrawals.insert(token_address, (period_start, withdrawn));
         }
 
 
-        #[ink(message)]
-        pub fn remove_vault_token(&mut self,erc_20_address: AccountId) -> bool  {
+        /// store the withdraw request, it has to be confirmed by moderators other than the submitter
+        fn submit_withdraw_request(&mut self, token_address: Option<AccountId>, to_address: AccountId, value: Balance, kind: WithdrawalKind) -> RequestId {
 
-            let caller = self.env().caller();
+            let submitter = self.env().caller();
+            let submit_time = self.env().block_timestamp();
+            let request_id = self.withdraw_requests_length;
+            self.withdraw_requests_length += 1;
 
-            let  auth = self.get_auth_by_address(self.auth_contract_address);
+            self.withdraw_requests.insert(request_id, WithdrawRequest{
+                request_id:request_id,
+                token_address:token_address,
+                to_address:to_address,
+                value:value,
+                kind:kind,
+                submitter:submitter,
+                submit_time:submit_time,
+                expire_time:if self.request_lifetime == 0 { None } else { Some(submit_time.saturating_add(self.request_lifetime)) },
+                executed:false,
+            });
 
-            let is_permission = auth.has_permission(caller,String::from("vault"),String::from("remove_vault_token"));
+            self.env().emit_event(SubmitWithdrawRequestEvent{
+                request_id:request_id,
+                submitter:submitter,});
 
-            if is_permission == false {
-                return false;
-            }
+            request_id
+        }
 
 
+        fn confirm_by_caller(&mut self, moderator: AccountId, request_id: RequestId) -> ConfirmationStatus {
+            let new_confirmation = self.request_confirmations.insert((request_id, moderator), ()).is_none();
+            let status = self.confirmation_status(request_id);
+            if new_confirmation {
+                self.env().emit_event(ConfirmWithdrawRequestEvent{
+                    request_id:request_id,
+                    moderator:moderator,
+                    status:status,});
+            }
+            status
+        }
 
-            match self.visible_tokens.take(&erc_20_address) {
-                None => { false}
-                Some(_) => {
 
-                    self.env().emit_event(RemoveVaultTokenEvent{
-                        token_address:erc_20_address,
-                        });
-                    true
-                }
+        /// confirmations are counted for the current moderators only
+        fn confirmation_status(&self, request_id: RequestId) -> ConfirmationStatus {
+            let count = self.get_withdraw_request_confirmations(request_id).len() as u32;
+            if count >= self.required_confirmations {
+                ConfirmationStatus::Confirmed
+            } else {
+                ConfirmationStatus::ConfirmationsNeeded(self.required_confirmations - count)
             }
         }
 
 
-        #[ink(message)]
-        pub fn get_token_list(&self) -> ink_prelude::vec::Vec<AccountId> {
-            self.visible_tokens.keys();
-            let mut v:ink_prelude::vec::Vec<AccountId> = ink_prelude::vec::Vec::new();
-            for key in self.visible_tokens.keys() {
-                v.push(*key)
+        /// whether the request exists, is not executed and has not expired
+        fn is_request_open(&self, request_id: RequestId) -> bool {
+            match self.withdraw_requests.get(&request_id) {
+                Some(request) => !request.executed && request.expire_time.map_or(true, |expire_time| self.env().block_timestamp() < expire_time),
+                None => false,
             }
-            v
         }
 
 
+        fn is_moderator(&self, account: AccountId) -> bool {
+            self.moderator_list().contains(&account)
+        }
 
-        #[ink(message)]
-        pub fn get_balance_of(&self,erc_20_address: AccountId) -> u64 {
 
-            if self.tokens.contains_key(&erc_20_address) {
+        // calls to the auth, org and token contracts, off-chain tests call the `mock` module instead
 
-               // let mut erc_20 = self.get_erc20_by_address(*erc_20_address.unwrap());
-                let  erc_20 = self.get_erc20_by_address(erc_20_address);
-                //let token_name = (&erc_20).name();
-                let balanceof = erc_20.balance_of(self.vault_contract_address);
+        #[cfg(not(test))]
+        fn has_permission(&self, account: AccountId, contract_name: String, function_name: String) -> bool {
+            self.get_auth_by_address(self.auth_contract_address).has_permission(account, contract_name, function_name)
+        }
 
 
-                self.env().emit_event(GetTokenBalanceEvent{
-                    token_address:erc_20_address,
-                    balance:balanceof,});
+        #[cfg(not(test))]
+        fn moderator_list(&self) -> ink_prelude::vec::Vec<AccountId> {
+            self.get_orgmanager_by_address(self.org_contract_address).get_dao_moderator_list()
+        }
 
-                balanceof
 
-            } else{
-                0
-            }
+        /// name, symbol and decimals of a token
+        #[cfg(not(test))]
+        fn token_metadata(&self, erc_20_address: AccountId) -> (String, String, u8) {
+            let  erc_20 = self.get_erc20_by_address(erc_20_address);
+            (erc_20.name(), erc_20.symbol(), erc_20.decimals())
         }
 
 
-        #[ink(message)]
-        pub fn deposit(&mut self, erc_20_address:AccountId, from_address:AccountId,value:u64) -> bool {
+        /// balance of a token held by the vault
+        #[cfg(not(test))]
+        fn token_balance(&self, erc_20_address: AccountId) -> u64 {
+            self.get_erc20_by_address(erc_20_address).balance_of(self.vault_contract_address)
+        }
 
-            let to_address = self.vault_contract_address;
 
-            if self.tokens.contains_key(&erc_20_address) {
+        #[cfg(not(test))]
+        fn token_transfer(&mut self, erc_20_address: AccountId, to_address: AccountId, value: u64) -> bool {
+            self.get_erc20_by_address(erc_20_address).transfer(to_address, value)
+        }
 
-                // let  balanceof =  self.get_balance_of(erc_20_address);
 
+        #[cfg(not(test))]
+        fn token_transfer_from(&mut self, erc_20_address: AccountId, from_address: AccountId, to_address: AccountId, value: u64) -> bool {
+            self.get_erc20_by_address(erc_20_address).transfer_from(from_address, to_address, value)
+        }
 
-                //let mut erc_20 = self.get_erc20_by_address(*erc_20_address.unwrap());
-                let mut erc_20 = self.get_erc20_by_address(erc_20_address);
 
-                let token_name = (&erc_20).name();
+        #[cfg(test)]
+        fn has_permission(&self, account: AccountId, contract_name: String, function_name: String) -> bool {
+            mock::has_permission(account, &contract_name, &function_name)
+        }
 
-                
-                let transfer_result = erc_20.transfer_from(from_address,to_address, value);
 
-                if transfer_result == false {
-                    return false;
-                }
+        #[cfg(test)]
+        fn moderator_list(&self) -> ink_prelude::vec::Vec<AccountId> {
+            mock::moderator_list()
+        }
 
-                let transfer_id:u64 = (self.transfer_history.len()+1).into();
 
-                let transfer_time: u64 = self.env().block_timestamp();
+        #[cfg(test)]
+        fn token_metadata(&self, erc_20_address: AccountId) -> (String, String, u8) {
+            mock::token_metadata(erc_20_address)
+        }
 
 
-                self.transfer_history.insert(transfer_id,
-                                             Transfer{
+        #[cfg(test)]
+        fn token_balance(&self, erc_20_address: AccountId) -> u64 {
+            mock::balance_of(erc_20_address, self.vault_contract_address)
+        }
 
-                                                 transfer_direction:2,// 1: out 2: in
-                                                 token_name:token_name.clone(),
-                                                 transfer_id:transfer_id,
-                                                 from_address:from_address,
-                                                 to_address:to_address,
-                                                 value,
-                                                 transfer_time});
 
+        #[cfg(test)]
+        fn token_transfer(&mut self, erc_20_address: AccountId, to_address: AccountId, value: u64) -> bool {
+            mock::transfer(erc_20_address, self.vault_contract_address, to_address, value)
+        }
 
-                self.env().emit_event(DepositTokenEvent{
-                    token_name: token_name.clone(),
-                    from_address:from_address,
-                    value:value});
-                true
 
-            } else{
-                false
-            }
+        #[cfg(test)]
+        fn token_transfer_from(&mut self, erc_20_address: AccountId, from_address: AccountId, to_address: AccountId, value: u64) -> bool {
+            mock::transfer_from(erc_20_address, self.vault_contract_address, from_address, to_address, value)
         }
 
 
+        fn transfer_matches(&self, transfer: &Transfer, filter: &TransferFilter) -> bool {
+            let counterparty = if transfer.from_address == self.vault_contract_address { transfer.to_address } else { transfer.from_address };
+            filter.token_address.map_or(true, |token_address| token_address == transfer.token_address)
+                && filter.counterparty.map_or(true, |account| account == counterparty)
+                && filter.direction.map_or(true, |direction| direction == transfer.transfer_direction)
+                && filter.from_time.map_or(true, |from_time| transfer.transfer_time >= from_time)
+                && filter.to_time.map_or(true, |to_time| transfer.transfer_time < to_time)
+        }
 
-        #[ink(message)]
-        pub fn withdraw(&mut self,erc_20_address:AccountId,to_address:AccountId,value:u64) -> bool {
 
-            let from_address = self.vault_contract_address;
+        fn index_length(&self, index: &TransferIndex) -> u64 {
+            *self.transfer_index_length.get(index).unwrap_or(&0)
+        }
 
-            if self.visible_tokens.contains_key(&erc_20_address) {
 
+        /// position of the first transfer in the index whose id is `transfer_id` or above
+        fn index_position(&self, index: &TransferIndex, transfer_id: TransferId) -> u64 {
+            let (mut low, mut high) = (0, self.index_length(index));
+            while low < high {
+                let middle = low + (high - low) / 2;
+                if *self.transfer_index.get(&(index.clone(), middle)).unwrap() < transfer_id {
+                    low = middle + 1;
+                } else {
+                    high = middle;
+                }
+            }
+            low
+        }
 
-                let caller = self.env().caller();
 
-                let  auth = self.get_auth_by_address(self.auth_contract_address);
-    
-                let is_permission = auth.has_permission(caller,String::from("vault"),String::from("withdraw"));
-    
-                if is_permission == false {
-                    return false;
+        /// id of the first transfer at or after the time, the next id if there is none
+        fn first_transfer_at(&self, time: u64) -> TransferId {
+            let (mut low, mut high) = (1, self.transfer_count + 1);
+            while low < high {
+                let middle = low + (high - low) / 2;
+                if self.transfer_history.get(&middle).unwrap().transfer_time < time {
+                    low = middle + 1;
+                } else {
+                    high = middle;
                 }
+            }
+            low
+        }
 
 
+        fn record_transfer(&mut self, transfer_direction: Direction, token_address: Option<AccountId>, token_name: String, from_address: AccountId, to_address: AccountId, value: Balance) {
 
-                // let  balanceof =  self.get_balance_of(erc_20_address);
+            self.transfer_count += 1;
+            let transfer_id = self.transfer_count;
 
+            let transfer_time: u64 = self.env().block_timestamp();
 
-                //let mut erc_20 = self.get_erc20_by_address(*erc_20_address.unwrap());
-                let mut erc_20 = self.get_erc20_by_address(erc_20_address);
+            let counterparty = if from_address == self.vault_contract_address { to_address } else { from_address };
+            for index in ink_prelude::vec![
+                TransferIndex::Token(token_address),
+                TransferIndex::Counterparty(counterparty),
+                TransferIndex::Direction(transfer_direction),
+            ] {
+                let length = self.transfer_index_length.entry(index.clone()).or_insert(0);
+                let position = *length;
+                *length += 1;
+                self.transfer_index.insert((index, position), transfer_id);
+            }
 
-                let token_name = (&erc_20).name();
+            // withdrawals of a balance which was never recorded, like the native endowment, clamp
+            // at 0 and show up as external inflows when reconciled
+            let recorded = self.recorded_balances.entry(token_address).or_insert(0);
+            match transfer_direction {
+                Direction::In | Direction::ExternalIn => *recorded += value,
+                Direction::Out | Direction::ExternalOut => *recorded = recorded.saturating_sub(value),
+                Direction::Reserve | Direction::Release => {}
+            }
 
-                //erc_20.transfer_from(from_address,to_address, value);
-                
-                let transfer_result  = erc_20.transfer(to_address, value);
+            self.transfer_history.insert(transfer_id,
+                                         Transfer{
+                                             transfer_direction:transfer_direction,
+                                             token_address:token_address,
+                                             token_name:token_name,
+                                             transfer_id:transfer_id,
+                                             from_address:from_address,
+                                             to_address:to_address,
+                                             value:value,
+                                             transfer_time:transfer_time});
+        }
 
-                if transfer_result == false {
-                    return false;
-                }
+    }
 
-                let transfer_id:u64 = (self.transfer_history.len()+1).into();
+    /// start of the period `now` falls in, periods follow each other from `start_time`
+    fn current_period_start(start_time: u64, period: u64, now: u64) -> u64 {
+        if now < start_time {
+            return start_time;
+        }
+        start_time + (now - start_time) / period * period
+    }
 
-                let transfer_time: u64 = self.env().block_timestamp();
+    /// part of the total vested at `now`, linearly from the start to the end time and nothing before the cliff
+    fn vested_amount(total: Balance, start_time: u64, end_time: u64, cliff_time: Option<u64>, now: u64) -> Balance {
+        if now < start_time || cliff_time.map_or(false, |cliff_time| now < cliff_time) {
+            return 0;
+        }
+        if now >= end_time {
+            return total;
+        }
+        total * (now - start_time) as Balance / (end_time - start_time) as Balance
+    }
 
-                self.transfer_history.insert(transfer_id,
-                                             Transfer{
-                                                 transfer_direction:1,// 1: out 2: in
-                                                 token_name: token_name.clone(),
-                                                 transfer_id:transfer_id,
-                                                 from_address:from_address,
-                                                 to_address:to_address,
-                                                 value:value,
-                                                 transfer_time:transfer_time});
+    /// State of the auth, org and token contracts the vault calls in off-chain tests.
+    #[cfg(test)]
+    mod mock {
+        use super::*;
+        use std::{
+            cell::RefCell,
+            collections::{BTreeMap, BTreeSet},
+        };
 
+        #[derive(Default)]
+        struct State {
+            permissions: BTreeSet<(AccountId, String, String)>,
+            moderators: ink_prelude::vec::Vec<AccountId>,
+            tokens: BTreeMap<AccountId, (String, String, u8)>,
+            balances: BTreeMap<(AccountId, AccountId), u64>,
+            allowances: BTreeMap<(AccountId, AccountId, AccountId), u64>,
+        }
 
+        thread_local! {
+            static STATE: RefCell<State> = RefCell::new(State::default());
+        }
 
+        pub fn reset() {
+            STATE.with(|state| *state.borrow_mut() = State::default());
+        }
 
-                self.env().emit_event(WithdrawTokenEvent{
-                    token_name: token_name.clone(),
-                    to_address:to_address,
-                    value:value,});
+        pub fn grant(account: AccountId, function_name: &str) {
+            STATE.with(|state| state.borrow_mut().permissions.insert((account, String::from("vault"), String::from(function_name))));
+        }
 
-                true
+        pub fn set_moderators(moderators: &[AccountId]) {
+            STATE.with(|state| state.borrow_mut().moderators = moderators.to_vec());
+        }
 
-            } else{
-                false
-            }
+        /// create a token, the owner holds the supply
+        pub fn create_token(erc_20_address: AccountId, name: &str, owner: AccountId, supply: u64) {
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+                state.tokens.insert(erc_20_address, (String::from(name), name.to_uppercase(), 18));
+                state.balances.insert((erc_20_address, owner), supply);
+            });
         }
 
+        pub fn approve(erc_20_address: AccountId, owner: AccountId, spender: AccountId, value: u64) {
+            STATE.with(|state| state.borrow_mut().allowances.insert((erc_20_address, owner, spender), value));
+        }
 
+        pub fn has_permission(account: AccountId, contract_name: &str, function_name: &str) -> bool {
+            STATE.with(|state| state.borrow().permissions.contains(&(account, String::from(contract_name), String::from(function_name))))
+        }
 
-        #[ink(message)]
-        pub fn get_transfer_history(&self) -> ink_prelude::vec::Vec<Transfer> {
-            let mut temp_vec = ink_prelude::vec::Vec::new();
-            let mut iter = self.transfer_history.values();
-            let mut temp = iter.next();
-            while temp.is_some() {
-                temp_vec.push(temp.unwrap().clone());
-                temp = iter.next();
-            }
-            temp_vec
+        pub fn moderator_list() -> ink_prelude::vec::Vec<AccountId> {
+            STATE.with(|state| state.borrow().moderators.clone())
         }
 
+        pub fn token_metadata(erc_20_address: AccountId) -> (String, String, u8) {
+            STATE.with(|state| state.borrow().tokens.get(&erc_20_address).cloned().unwrap_or_default())
+        }
+
+        pub fn balance_of(erc_20_address: AccountId, owner: AccountId) -> u64 {
+            STATE.with(|state| *state.borrow().balances.get(&(erc_20_address, owner)).unwrap_or(&0))
+        }
+
+        pub fn transfer(erc_20_address: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let from_balance = balance_of(erc_20_address, from);
+            if from_balance < value {
+                return false;
+            }
+            let to_balance = balance_of(erc_20_address, to);
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+                state.balances.insert((erc_20_address, from), from_balance - value);
+                state.balances.insert((erc_20_address, to), to_balance + value);
+            });
+            true
+        }
 
+        pub fn transfer_from(erc_20_address: AccountId, spender: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let allowance = STATE.with(|state| *state.borrow().allowances.get(&(erc_20_address, from, spender)).unwrap_or(&0));
+            if allowance < value || !transfer(erc_20_address, from, to, value) {
+                return false;
+            }
+            approve(erc_20_address, from, spender, allowance - value);
+            true
+        }
     }
 
     /// Unit tests
@@ -398,14 +2389,49 @@ mod vault {
         };
         use ink_lang as ink;
 
+        /// a vault of the org django, with the auth contract frank, calling the reset mocks
+        fn new_vault_manager() -> VaultManager {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            mock::reset();
+            VaultManager::new(accounts.django,accounts.frank)
+        }
+
+
+        /// list the token at the address, alice holds its supply and approves it to the vault
+        fn add_token(vault_manager: &mut VaultManager, erc_20_address: AccountId, name: &str, supply: u64) {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            mock::create_token(erc_20_address, name, accounts.alice, supply);
+            mock::approve(erc_20_address, accounts.alice, AccountId::from([0x07; 32]), supply);
+            mock::grant(accounts.alice, "add_vault_token");
+            assert!(vault_manager.add_vault_token(erc_20_address));
+        }
+
+
+        fn set_next_caller(caller: AccountId) {
+            test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                AccountId::from([0x07; 32]),
+                1_000_000,
+                0,
+                test::CallData::new(call::Selector::new([0x00; 4])),
+            );
+        }
+
+
         #[ink::test]
         fn new_vault_works() {
             let accounts =
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            assert_eq!(vault_manager.org_id, 1);
+            let vault_manager = new_vault_manager();
+            assert_eq!(vault_manager.org_contract_address, accounts.django);
+            assert_eq!(vault_manager.auth_contract_address, accounts.frank);
+            assert_eq!(vault_manager.vault_contract_address, AccountId::from([0x07; 32]));
         }
 
         #[ink::test]
@@ -414,10 +2440,14 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
+            let mut vault_manager = new_vault_manager();
+            mock::create_token(accounts.bob, "eth", accounts.alice, 0);
+            assert!(!vault_manager.add_vault_token(accounts.bob));
+            mock::grant(accounts.alice, "add_vault_token");
+            assert!(vault_manager.add_vault_token(accounts.bob));
+            assert!(!vault_manager.add_vault_token(accounts.bob));
             assert_eq!(vault_manager.tokens.len(), 1);
+            assert_eq!(vault_manager.token_name(Some(accounts.bob)), "eth");
         }
 
 
@@ -427,12 +2457,12 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
-            vault_manager.remove_vault_token(accounts.bob);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 0);
+            mock::grant(accounts.alice, "remove_vault_token");
+            assert!(vault_manager.remove_vault_token(accounts.bob));
             assert_eq!(vault_manager.tokens.len(), 1);
-            assert_eq!(vault_manager.visible_tokens.len(), 0);
+            assert_eq!(vault_manager.get_token_list().len(), 0);
         }
 
 
@@ -442,11 +2472,9 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
-            let dot_name = String::from("dot");
-            vault_manager.add_vault_token(dot_name,accounts.alice);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 0);
+            add_token(&mut vault_manager, accounts.alice, "dot", 0);
             assert_eq!(vault_manager.get_token_list().len(), 2);
         }
 
@@ -457,9 +2485,8 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 0);
             assert_eq!(vault_manager.get_balance_of(accounts.bob), 0);
         }
 
@@ -471,11 +2498,12 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
-            vault_manager.deposit(accounts.bob,accounts.alice,100);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 1000);
+            assert!(vault_manager.deposit(accounts.bob,100));
             assert_eq!(vault_manager.get_balance_of(accounts.bob),100);
+            // more than the allowance left
+            assert!(!vault_manager.deposit(accounts.bob,1000));
 
         }
 
@@ -486,11 +2514,12 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
-            vault_manager.deposit(accounts.bob,accounts.eve,1000);
-            vault_manager.withdraw(accounts.bob,accounts.alice,100);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 1000);
+            vault_manager.deposit(accounts.bob,1000);
+            assert_eq!(vault_manager.withdraw(accounts.bob,accounts.alice,100), None);
+            mock::grant(accounts.alice, "withdraw");
+            assert_eq!(vault_manager.withdraw(accounts.bob,accounts.alice,100), Some(WithdrawOutcome::Done(2)));
             assert_eq!(vault_manager.get_balance_of(accounts.bob),900);
 
         }
@@ -502,14 +2531,431 @@ mod vault {
                 ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                     .expect("Cannot get accounts");
             // Create a new contract instance.
-            let mut vault_manager = VaultManager::new(1);
-            let eth_name = String::from("eth");
-            vault_manager.add_vault_token(eth_name,accounts.bob);
-            vault_manager.deposit(accounts.bob,accounts.eve,1000);
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 1000);
+            mock::grant(accounts.alice, "withdraw");
+            vault_manager.deposit(accounts.bob,1000);
             vault_manager.withdraw(accounts.bob,accounts.alice,100);
             assert_eq!(vault_manager.get_transfer_history().len(),2);
 
         }
 
+
+        #[ink::test]
+        fn token_lifecycle_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            add_token(&mut vault_manager, accounts.bob, "eth", 1000);
+            add_token(&mut vault_manager, accounts.charlie, "dot", 0);
+            for function_name in &["withdraw", "remove_vault_token", "unhide_vault_token", "delist_vault_token"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            assert!(vault_manager.deposit(accounts.bob, 300));
+            let eth = TokenInfo{
+                token_address: accounts.bob,
+                name: String::from("eth"),
+                symbol: String::from("ETH"),
+                decimals: 18,
+                status: TokenStatus::Visible,
+            };
+            assert_eq!(vault_manager.get_token_info(accounts.bob), Some(eth.clone()));
+
+            // hidden tokens accept deposits, not withdrawals
+            assert!(vault_manager.remove_vault_token(accounts.bob));
+            assert!(!vault_manager.remove_vault_token(accounts.bob));
+            assert_eq!(vault_manager.get_token_list(), vec![accounts.charlie]);
+            assert_eq!(vault_manager.get_token_details()[0].token.status, TokenStatus::Hidden);
+            assert!(vault_manager.deposit(accounts.bob, 100));
+            assert_eq!(vault_manager.withdraw(accounts.bob, accounts.alice, 100), None);
+
+            assert!(vault_manager.unhide_vault_token(accounts.bob));
+            assert!(!vault_manager.unhide_vault_token(accounts.bob));
+            assert_eq!(vault_manager.get_token_list(), vec![accounts.bob, accounts.charlie]);
+
+            // delisted tokens accept neither, and are out of the details
+            assert!(vault_manager.delist_vault_token(accounts.bob));
+            assert!(!vault_manager.unhide_vault_token(accounts.bob));
+            assert!(!vault_manager.remove_vault_token(accounts.bob));
+            assert_eq!(vault_manager.get_token_list(), vec![accounts.charlie]);
+            assert_eq!(vault_manager.get_token_details().len(), 1);
+            assert!(!vault_manager.deposit(accounts.bob, 100));
+            assert_eq!(vault_manager.withdraw(accounts.bob, accounts.alice, 100), None);
+
+            // listed again, in its first place
+            assert!(vault_manager.add_vault_token(accounts.bob));
+            assert_eq!(vault_manager.get_token_list(), vec![accounts.bob, accounts.charlie]);
+            assert_eq!(vault_manager.get_token_details(), vec![
+                TokenDetails{token: eth, balance: 400, reserved: 0},
+                TokenDetails{
+                    token: TokenInfo{
+                        token_address: accounts.charlie,
+                        name: String::from("dot"),
+                        symbol: String::from("DOT"),
+                        decimals: 18,
+                        status: TokenStatus::Visible,
+                    },
+                    balance: 0,
+                    reserved: 0,
+                },
+            ]);
+        }
+
+
+        #[ink::test]
+        fn withdraw_requests_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            for function_name in &["withdraw", "set_withdraw_threshold", "set_withdraw_request_rules"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            mock::set_moderators(&[accounts.alice, accounts.bob, accounts.charlie]);
+            assert!(vault_manager.set_withdraw_threshold(None, Some(100)));
+            // more confirmations than there are moderators
+            assert!(!vault_manager.set_withdraw_request_rules(4, 0, 10));
+            assert!(vault_manager.set_withdraw_request_rules(1, 0, 10));
+
+            // the second 60 takes the period above the threshold
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 60), Some(WithdrawOutcome::Done(1)));
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 60), Some(WithdrawOutcome::Requested(0)));
+
+            // the submitter's confirmation doesn't count
+            assert_eq!(vault_manager.confirm_withdraw_request(0), None);
+            assert!(!vault_manager.execute_withdraw_request(0));
+            set_next_caller(accounts.bob);
+            assert_eq!(vault_manager.confirm_withdraw_request(0), Some(ConfirmationStatus::Confirmed));
+            assert!(vault_manager.execute_withdraw_request(0));
+            assert_eq!(vault_manager.get_native_balance(), 880);
+
+            // a new period starts at 10
+            set_next_caller(accounts.alice);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 60), Some(WithdrawOutcome::Done(3)));
+            assert_eq!(vault_manager.get_native_balance(), 820);
+        }
+
+
+        #[ink::test]
+        fn create_stream_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            for function_name in &["create_stream", "set_withdraw_threshold", "set_timelock"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            mock::set_moderators(&[accounts.alice, accounts.bob]);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+
+            // it is 10, a stream can't start in the past
+            assert_eq!(vault_manager.create_stream(accounts.bob, None, 100, 5, 20, None), None);
+            assert_eq!(vault_manager.create_stream(accounts.bob, None, 100, 10, 20, None), Some(WithdrawOutcome::Done(0)));
+
+            // the totals need confirmations like withdrawals
+            assert!(vault_manager.set_withdraw_threshold(None, Some(50)));
+            assert_eq!(vault_manager.create_stream(accounts.bob, None, 100, 10, 20, None), Some(WithdrawOutcome::Requested(0)));
+            assert_eq!(vault_manager.get_withdraw_request(0).unwrap().kind, WithdrawalKind::Stream{start_time: 10, end_time: 20, cliff_time: None});
+            assert_eq!(vault_manager.get_stream(1), None);
+            set_next_caller(accounts.bob);
+            assert_eq!(vault_manager.confirm_withdraw_request(0), Some(ConfirmationStatus::Confirmed));
+            assert!(vault_manager.execute_withdraw_request(0));
+            assert_eq!(vault_manager.get_stream(1).unwrap().total, 100);
+
+            // and are timelocked like withdrawals
+            set_next_caller(accounts.alice);
+            assert!(vault_manager.set_withdraw_threshold(None, None));
+            assert!(vault_manager.set_timelock_delay(10));
+            assert_eq!(vault_manager.create_stream(accounts.bob, None, 100, 20, 30, None), Some(WithdrawOutcome::Queued(0)));
+            assert!(!vault_manager.execute_queued_withdrawal(0));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert!(vault_manager.execute_queued_withdrawal(0));
+            assert_eq!(vault_manager.get_stream(2).unwrap().start_time, 20);
+            assert_eq!(*vault_manager.reserved.get(&None).unwrap(), 300);
+        }
+
+
+        #[ink::test]
+        fn timelock_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            for function_name in &["withdraw", "set_timelock", "cancel_queued_withdrawal"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            assert!(vault_manager.set_timelock_delay(10));
+            assert!(vault_manager.set_timelock_limit(None, Some(100)));
+
+            // up to the limit withdrawals are paid at once
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 100), Some(WithdrawOutcome::Done(1)));
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 200), Some(WithdrawOutcome::Queued(0)));
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 300), Some(WithdrawOutcome::Queued(1)));
+            assert_eq!(vault_manager.get_queued_withdrawal(0).unwrap().eta, 10);
+            assert_eq!(vault_manager.get_native_balance(), 900);
+
+            // nothing runs before the eta, the first one is cancelled
+            assert!(!vault_manager.execute_queued_withdrawal(0));
+            set_next_caller(accounts.bob);
+            assert!(!vault_manager.cancel_queued_withdrawal(0));
+            set_next_caller(accounts.alice);
+            assert!(vault_manager.cancel_queued_withdrawal(0));
+            assert_eq!(vault_manager.get_queued_withdrawal(0).unwrap().state, QueuedWithdrawalState::Cancelled);
+
+            // from the eta on anyone executes the second one, but can't cancel it
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert!(!vault_manager.cancel_queued_withdrawal(1));
+            assert!(!vault_manager.execute_queued_withdrawal(0));
+            set_next_caller(accounts.bob);
+            assert!(vault_manager.execute_queued_withdrawal(1));
+            assert!(!vault_manager.execute_queued_withdrawal(1));
+            assert_eq!(vault_manager.get_queued_withdrawal(1).unwrap().state, QueuedWithdrawalState::Executed);
+            assert_eq!(vault_manager.get_native_balance(), 600);
+
+            // a delay past the end of time queues for good
+            set_next_caller(accounts.alice);
+            assert!(vault_manager.set_timelock_delay(u64::MAX));
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 200), Some(WithdrawOutcome::Queued(2)));
+            assert_eq!(vault_manager.get_queued_withdrawal(2).unwrap().eta, u64::MAX);
+        }
+
+
+        #[ink::test]
+        fn budget_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            for function_name in &["set_budget", "set_withdraw_threshold", "set_withdraw_request_rules"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            mock::set_moderators(&[accounts.alice, accounts.bob, accounts.charlie]);
+            assert!(vault_manager.set_withdraw_threshold(None, Some(100)));
+            assert!(vault_manager.set_withdraw_request_rules(1, 0, 10));
+            assert!(vault_manager.set_budget(BudgetHolder::Account(accounts.bob), None, 200, 100));
+
+            // a role holds at most MAX_ROLE_BUDGETS budgets of a token
+            let role = |i: u8| BudgetHolder::Role { contract_name: String::from("vault"), function_name: ink_prelude::format!("role{}", i) };
+            for i in 0..MAX_ROLE_BUDGETS as u8 {
+                assert!(vault_manager.set_budget(role(i), None, 50, 100));
+            }
+            assert!(!vault_manager.set_budget(role(MAX_ROLE_BUDGETS as u8), None, 50, 100));
+            assert!(vault_manager.remove_budget(role(0), None));
+            assert!(vault_manager.set_budget(role(MAX_ROLE_BUDGETS as u8), None, 50, 100));
+            assert_eq!(vault_manager.get_budgets().len(), MAX_ROLE_BUDGETS + 1);
+
+            // budget withdrawals count against the threshold, the second one is requested
+            set_next_caller(accounts.bob);
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 60), Some(WithdrawOutcome::Done(1)));
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 60), Some(WithdrawOutcome::Requested(0)));
+            assert_eq!(vault_manager.get_budget(BudgetHolder::Account(accounts.bob), None).unwrap().spent, 120);
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 90), None);
+            assert_eq!(vault_manager.get_native_balance(), 940);
+
+            // a role budget pays the holders of its permission
+            set_next_caller(accounts.charlie);
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 10), None);
+            mock::grant(accounts.charlie, "role1");
+            assert_eq!(vault_manager.withdraw_native(accounts.eve, 10), Some(WithdrawOutcome::Done(2)));
+            assert_eq!(vault_manager.get_budget(role(1), None).unwrap().spent, 10);
+            assert_eq!(vault_manager.get_native_balance(), 930);
+        }
+
+
+        #[test]
+        fn current_period_start_works() {
+            assert_eq!(current_period_start(100, 30, 100), 100);
+            assert_eq!(current_period_start(100, 30, 129), 100);
+            assert_eq!(current_period_start(100, 30, 130), 130);
+            assert_eq!(current_period_start(100, 30, 195), 190);
+        }
+
+
+        #[test]
+        fn vested_amount_works() {
+            assert_eq!(vested_amount(1000, 100, 200, None, 50), 0);
+            assert_eq!(vested_amount(1000, 100, 200, None, 150), 500);
+            assert_eq!(vested_amount(1000, 100, 200, Some(160), 150), 0);
+            assert_eq!(vested_amount(1000, 100, 200, Some(160), 160), 600);
+            assert_eq!(vested_amount(1000, 100, 200, None, 250), 1000);
+        }
+
+
+        #[ink::test]
+        fn deposit_native_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                accounts.eve,
+                AccountId::from([0x07; 32]),
+                1_000_000,
+                100,
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])),
+            );
+            assert!(vault_manager.deposit_native());
+            let history = vault_manager.get_transfer_history();
+            assert_eq!(history[0].token_address, None);
+            assert_eq!(history[0].from_address, accounts.eve);
+            assert_eq!(history[0].value, 100);
+
+        }
+
+
+        #[ink::test]
+        fn add_payroll_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            for function_name in &["set_payroll", "set_withdraw_threshold", "set_timelock"] {
+                mock::grant(accounts.alice, function_name);
+            }
+            mock::set_moderators(&[accounts.alice, accounts.bob]);
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+
+            // it is 5, the first payment can't be due in the past
+            assert_eq!(vault_manager.add_payroll(accounts.bob, None, 300, 10, 0), None);
+            assert_eq!(vault_manager.add_payroll(accounts.bob, None, 300, 10, 5), Some(WithdrawOutcome::Done(0)));
+
+            // the amount needs confirmations like a withdrawal
+            assert!(vault_manager.set_withdraw_threshold(None, Some(100)));
+            assert_eq!(vault_manager.add_payroll(accounts.eve, None, 300, 10, 5), Some(WithdrawOutcome::Requested(0)));
+            assert_eq!(vault_manager.get_payrolls().len(), 1);
+            set_next_caller(accounts.bob);
+            assert_eq!(vault_manager.confirm_withdraw_request(0), Some(ConfirmationStatus::Confirmed));
+            assert!(vault_manager.execute_withdraw_request(0));
+            assert_eq!(vault_manager.get_payroll(1).unwrap().recipient, accounts.eve);
+
+            // and is timelocked like a withdrawal, the entry is due from when it is added
+            set_next_caller(accounts.alice);
+            assert!(vault_manager.set_withdraw_threshold(None, None));
+            assert!(vault_manager.set_timelock_delay(10));
+            assert_eq!(vault_manager.add_payroll(accounts.eve, None, 300, 10, 5), Some(WithdrawOutcome::Queued(0)));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert!(vault_manager.execute_queued_withdrawal(0));
+            assert_eq!(vault_manager.get_payroll(2).unwrap().next_due, 15);
+        }
+
+
+        #[ink::test]
+        fn process_due_payments_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            mock::grant(accounts.alice, "set_payroll");
+            // bob is paid 300 every 10, eve's 5000 can't be paid
+            assert_eq!(vault_manager.add_payroll(accounts.bob, None, 300, 10, 0), Some(WithdrawOutcome::Done(0)));
+            assert_eq!(vault_manager.add_payroll(accounts.eve, None, 5000, 10, 0), Some(WithdrawOutcome::Done(1)));
+            assert_eq!(vault_manager.process_due_payments(10), 1);
+            assert_eq!(vault_manager.get_payroll(0).unwrap().next_due, 10);
+            assert_eq!(vault_manager.get_payroll(1).unwrap().next_due, 0);
+            let history = vault_manager.get_transfer_history();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].to_address, accounts.bob);
+            assert_eq!(history[0].value, 300);
+            // bob is not due again yet
+            assert_eq!(vault_manager.process_due_payments(10), 0);
+            assert_eq!(vault_manager.get_transfer_history().len(), 1);
+            // removed entries are not paid
+            assert!(vault_manager.remove_payroll(1));
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            assert_eq!(vault_manager.process_due_payments(10), 1);
+            assert_eq!(vault_manager.get_payrolls().len(), 1);
+            assert_eq!(vault_manager.get_native_balance(), 400);
+            // entries which aren't due count against max, the next call goes on from charlie
+            assert_eq!(vault_manager.add_payroll(accounts.charlie, None, 100, 10, 10), Some(WithdrawOutcome::Done(2)));
+            assert_eq!(vault_manager.process_due_payments(1), 0);
+            assert_eq!(vault_manager.process_due_payments(1), 1);
+            assert_eq!(vault_manager.get_native_balance(), 300);
+
+        }
+
+
+        #[ink::test]
+        fn reconcile_works() {
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 1000)
+                .expect("Cannot set balance");
+            assert_eq!(vault_manager.get_discrepancies(), vec![Discrepancy{token_address: None, recorded: 0, actual: 1000}]);
+            assert!(vault_manager.reconcile(None));
+            assert!(!vault_manager.reconcile(None));
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(AccountId::from([0x07; 32]), 400)
+                .expect("Cannot set balance");
+            assert!(vault_manager.reconcile(None));
+            let history = vault_manager.get_transfer_history();
+            assert_eq!(history[0].transfer_direction, Direction::ExternalIn);
+            assert_eq!(history[0].value, 1000);
+            assert_eq!(history[1].transfer_direction, Direction::ExternalOut);
+            assert_eq!(history[1].value, 600);
+            assert_eq!(vault_manager.get_discrepancies(), vec![Discrepancy{token_address: None, recorded: 400, actual: 400}]);
+
+        }
+
+
+        #[ink::test]
+        fn get_transfers_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            // Create a new contract instance.
+            let mut vault_manager = new_vault_manager();
+            // eve deposits at 0 and 10, bob at 5
+            for depositor in &[accounts.eve, accounts.bob, accounts.eve] {
+                ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                    *depositor,
+                    AccountId::from([0x07; 32]),
+                    1_000_000,
+                    100,
+                    ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])),
+                );
+                assert!(vault_manager.deposit_native());
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            }
+            let ids = |page: &TransferPage| page.transfers.iter().map(|transfer| transfer.transfer_id).collect::<ink_prelude::vec::Vec<TransferId>>();
+
+            let page = vault_manager.get_transfers(TransferFilter::default(), None, 2);
+            assert_eq!((ids(&page), page.next_cursor), (vec![1, 2], Some(3)));
+            let page = vault_manager.get_transfers(TransferFilter::default(), page.next_cursor, 2);
+            assert_eq!((ids(&page), page.next_cursor), (vec![3], None));
+
+            let from_eve = TransferFilter { counterparty: Some(accounts.eve), direction: Some(Direction::In), ..Default::default() };
+            assert_eq!(ids(&vault_manager.get_transfers(from_eve.clone(), None, 10)), vec![1, 3]);
+            assert_eq!(ids(&vault_manager.get_transfers(from_eve, Some(2), 10)), vec![3]);
+            let window = TransferFilter { from_time: Some(5), to_time: Some(10), ..Default::default() };
+            assert_eq!(ids(&vault_manager.get_transfers(window, None, 10)), vec![2]);
+            let tokens = TransferFilter { token_address: Some(Some(accounts.alice)), ..Default::default() };
+            assert_eq!(ids(&vault_manager.get_transfers(tokens, None, 10)), vec![]);
+        }
+
     }
 }
@@ -3,11 +3,11 @@
 extern crate alloc;
 use ink_lang as ink;
 pub use self::vote_manager::VoteManager;
+pub use self::vote_manager::ActionRules;
 
 #[ink::contract]
 mod vote_manager {
 
-    use alloc::format;
     use alloc::vec;
     use alloc::vec::Vec;
     use alloc::string::String;
@@ -15,17 +15,86 @@ mod vote_manager {
     use ink_storage::{
         collections::{
             HashMap as StorageHashMap,
-            Vec as StorageVec,
         },
         traits::{
             PackedLayout,
             SpreadLayout,
         }
     };
+    #[cfg(not(test))]
+    use ink_env::call::{
+        build_call,
+        utils::ReturnType,
+        ExecutionInput,
+    };
+    use ink_env::hash::Blake2x256;
+    #[cfg(not(test))]
+    use scale::Output;
+    #[cfg(not(test))]
+    use erc20::Erc20;
+    use org::OrgManager;
+    #[cfg(not(test))]
+    use auth::Auth;
 
     type VoteId = u64;
     type ChoiceId = u32;
 
+    /// Ratios are expressed in basis points, 10000 is 100%.
+    const BPS_BASE: u32 = 10_000;
+    /// Most votes returned by a page.
+    const MAX_PAGE_SIZE: u32 = 50;
+    /// Most votes looked at by a page, the page is cut short by filters matching few votes.
+    const MAX_PAGE_SCAN: u32 = 500;
+    /// Most live votes looked at by a sweep.
+    const MAX_SWEEP: u32 = 20;
+    /// Most delegations to one account, for any tag.
+    const MAX_DELEGATORS: u32 = 10;
+    /// Most tags one account delegates for.
+    const MAX_DELEGATION_TAGS: usize = 5;
+    /// Most delegations weight passes through to reach a voter's ballot, weight further down the
+    /// chain isn't counted unless a closer delegate votes.
+    const MAX_DELEGATION_DEPTH: u32 = 2;
+
+    /// A wrapper that allows us to encode a blob of bytes.
+    ///
+    /// We use this to pass the set of untyped (bytes) parameters to the `CallBuilder`.
+    #[cfg(not(test))]
+    struct CallInput<'a>(&'a [u8]);
+
+    #[cfg(not(test))]
+    impl<'a> scale::Encode for CallInput<'a> {
+        fn encode_to<T: Output>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// A cross-contract call which is dispatched when the vote is executed.
+    #[derive(scale::Encode, scale::Decode, Clone, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct VoteAction {
+        /// The AccountId of the contract that is called.
+        pub callee: AccountId,
+        /// The selector bytes that identifies the function of the callee that should be called.
+        pub selector: [u8; 4],
+        /// The SCALE encoded parameters that are passed to the called function.
+        pub input: Vec<u8>,
+        /// The amount of chain balance that is transferred to the callee.
+        pub transferred_value: Balance,
+        /// Gas limit for the execution of the call, 0 means all the remaining gas.
+        pub gas_limit: u64,
+        /// Whether the called function returns a bool, the call then only succeeds if it returns true.
+        pub returns_bool: bool,
+    }
+
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode, PackedLayout, SpreadLayout)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
     enum VoterState {
@@ -34,13 +103,12 @@ mod vote_manager {
         Nay,
     }
 
-    #[derive(scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[derive(scale::Encode, scale::Decode, Clone, SpreadLayout, PackedLayout)]
     #[cfg_attr(
         feature = "std",
         derive(
             Debug,
             PartialEq,
-            Clone,
             Eq,
             scale_info::TypeInfo,
             ink_storage::traits::StorageLayout
@@ -52,6 +120,163 @@ mod vote_manager {
         yea: u64,
     }
 
+    /// Rules a vote has to meet to pass, 0 disables a rule.
+    ///
+    /// Numbers count votes, which are token balances for token weighted votes.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct Threshold {
+        /// Minimum votes of the leading choice.
+        pub support_require_num: u64,
+        /// Minimum votes cast.
+        pub min_require_num: u64,
+        /// Minimum votes cast as a share of the electorate, in basis points.
+        pub min_participation_bps: u32,
+        /// Minimum votes of the leading choice as a share of the votes cast, in basis points.
+        pub min_approval_bps: u32,
+    }
+
+    /// Rules the votes dispatching a call have to meet, set when the vote module is created and
+    /// only changed by an executed vote.
+    ///
+    /// Their proposer has to be a member or moderator of the org, they have to be restricted to the
+    /// org members or weighted by the token, and their threshold can't be lower than the minimums.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub struct ActionRules {
+        /// Minimum votes cast as a share of the electorate, in basis points.
+        pub min_participation_bps: u32,
+        /// Minimum votes of the leading choice as a share of the votes cast, in basis points.
+        pub min_approval_bps: u32,
+        /// Token whose weighted votes may dispatch calls, besides the votes of the org members.
+        pub token: Option<AccountId>,
+    }
+
+    /// How the ballots of a vote are counted.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, PartialEq, Eq, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum TallyMethod {
+        /// One choice per ballot, the choice with the most votes wins.
+        Plurality,
+        /// Any number of choices per ballot, the choice approved by the most votes wins.
+        Approval,
+        /// Choices ranked by preference, counted by instant runoff.
+        RankedChoice,
+        /// Votes for a choice are bought with the vote's token, n votes cost n² tokens.
+        Quadratic,
+    }
+
+    /// A voter's ballot, its shape has to match the tally method of the vote.
+    #[derive(scale::Encode, scale::Decode, Clone, PartialEq, Eq, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum Ballot {
+        /// The choice voted for, for plurality votes.
+        Single(ChoiceId),
+        /// All the choices approved, for approval votes.
+        Approval(Vec<ChoiceId>),
+        /// Choices from the most to the least preferred, unranked choices are never counted,
+        /// for ranked-choice votes.
+        Ranked(Vec<ChoiceId>),
+        /// Votes to buy for a choice on top of those already bought, for quadratic votes.
+        Quadratic {
+            choice_id: ChoiceId,
+            votes: u64,
+        },
+    }
+
+    /// Phase of a vote's ballot casting, worked out from the time.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(Debug, scale_info::TypeInfo))]
+    pub enum VotePhase {
+        /// Ballots are cast openly.
+        Open,
+        /// Secret ballots are committed.
+        Commit,
+        /// Committed secret ballots are revealed and counted.
+        Reveal,
+        /// No more ballots.
+        Closed,
+    }
+
+    /// Lifecycle of a vote.
+    ///
+    /// A vote is `Pending` until its start date, `Active` while ballots are cast, committed or
+    /// revealed, then `Succeeded` or `Defeated` by its threshold. `Defeated`, `Cancelled`, `Vetoed`
+    /// and `Executed` are final.
+    #[derive(scale::Encode, scale::Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum VoteState {
+        Pending,
+        Active,
+        Succeeded,
+        Defeated,
+        Cancelled,
+        Vetoed,
+        Executed,
+    }
+
+    /// Who may vote, besides having voting power.
+    #[derive(scale::Encode, scale::Decode, Clone, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            PartialEq,
+            Eq,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    pub enum Eligibility {
+        /// Members and moderators of the org.
+        OrgMember(AccountId),
+        /// Accounts with the permission for the action in the auth contract.
+        AuthAction {
+            auth_address: AccountId,
+            contract_name: String,
+            function_name: String,
+        },
+    }
+
     #[derive(scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
     #[cfg_attr(
         feature = "std",
@@ -65,16 +290,38 @@ mod vote_manager {
     )]
     pub struct Vote {
         vote_id: VoteId,
-        executed: bool,
+        // Defeated, Cancelled, Vetoed or Executed once settled, the other states follow from the time and tallies
+        settled: Option<VoteState>,
+        proposer: AccountId,
         title: String,
         desc: String,
+        // ballots are accepted from the start date
         start_date: u64,
         vote_time: u64,
-        support_require_num: u64,
-        min_require_num: u64,
+        // secret ballots are revealed during this time after the vote time, open ballots if none
+        reveal_time: Option<u64>,
+        threshold: Threshold,
+        // votes the eligible voters could cast when the vote was created, 0 if unknown
+        electorate: u64,
+        tally_method: TallyMethod,
         support_num: u64,
-        choice_index_lo: u32,
-        choice_index_ho: u32,
+        // voters are stored by (vote_id, index) in the order of their first ballot
+        voter_num: u32,
+        // choices are stored by (vote_id, choice_id), choice_id from 0 to choice_num - 1
+        choice_num: u32,
+        // token whose balances weight the votes, one account one vote if none
+        erc20_address: Option<AccountId>,
+        // snapshot of the token balances taken when the vote was created
+        snapshot_id: u32,
+        // call dispatched on execution
+        action: Option<VoteAction>,
+        // whether the call succeeded, none until an action is executed
+        action_result: Option<bool>,
+        // anyone may vote if none
+        eligibility: Option<Eligibility>,
+        // delegations for this tag apply to the vote, besides the global ones
+        tag: Option<String>,
+        veto_reason: Option<String>,
     }
 
     #[derive(scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
@@ -90,27 +337,197 @@ mod vote_manager {
     )]
     pub struct DisplayVote {
         vote_id: VoteId,
-        executed: bool,
+        state: VoteState,
+        proposer: AccountId,
         title: String,
         desc: String,
         start_date: u64,
         vote_time: u64,
-        support_require_num: u64,
-        min_require_num: u64,
+        reveal_time: Option<u64>,
+        phase: VotePhase,
+        threshold: Threshold,
+        electorate: u64,
+        tally_method: TallyMethod,
         support_num: u64,
-        choices: String,
+        voter_num: u32,
+        // votes cast as a share of the electorate, in basis points
+        participation_bps: u32,
+        // votes of the winning choice as a share of the votes cast, in basis points
+        approval_bps: u32,
+        // none if nobody voted or the leading choices are tied
+        winner: Option<ChoiceId>,
+        choices: Vec<Choice>,
+        erc20_address: Option<AccountId>,
+        snapshot_id: u32,
+        action: Option<VoteAction>,
+        action_result: Option<bool>,
+        eligibility: Option<Eligibility>,
+        tag: Option<String>,
+        veto_reason: Option<String>,
+    }
+
+    /// A list of vote ids kept by position, from 0 to its length - 1.
+    ///
+    /// Votes are appended, and the last vote takes the position of a vote taken out.
+    #[derive(scale::Encode, scale::Decode, Clone, PartialEq, Eq, PartialOrd, Ord, SpreadLayout, PackedLayout)]
+    #[cfg_attr(
+        feature = "std",
+        derive(
+            Debug,
+            scale_info::TypeInfo,
+            ink_storage::traits::StorageLayout
+        )
+    )]
+    enum VoteIndex {
+        /// Every vote, by id, not stored.
+        All,
+        /// The pending and active votes, and those over since they were last swept.
+        Live,
+        /// The succeeded votes swept from the live ones, until executed or vetoed.
+        Succeeded,
+        /// The settled votes in a final state.
+        Settled(VoteState),
+        /// The votes of a proposer.
+        Proposer(AccountId),
+    }
+
+    /// Filters of `query_votes`, a vote has to match all those set.
+    #[derive(scale::Encode, scale::Decode, Clone, Default)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub struct VoteFilter {
+        pub state: Option<VoteState>,
+        pub proposer: Option<AccountId>,
+        /// Votes starting at or after this time.
+        pub start_from: Option<u64>,
+        /// Votes starting before this time.
+        pub start_to: Option<u64>,
+    }
+
+    /// A page of votes, and the cursor to query the next page from.
+    #[derive(scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub struct VotePage {
+        pub votes: Vec<DisplayVote>,
+        /// Position of the next vote in the votes queried, none if there are no more votes.
+        pub next_cursor: Option<u64>,
     }
 
+    /// Number of votes in each state.
+    #[derive(scale::Encode, scale::Decode, Default)]
+    #[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, scale_info::TypeInfo))]
+    pub struct VoteStateCount {
+        pub pending: u32,
+        pub active: u32,
+        pub succeeded: u32,
+        pub defeated: u32,
+        pub cancelled: u32,
+        pub vetoed: u32,
+        pub executed: u32,
+    }
 
     #[ink(storage)]
     pub struct VoteManager {
+        // the moderators of the org may veto votes
+        org: OrgManager,
+        org_address: AccountId,
+        // rules of the votes dispatching a call
+        action_rules: ActionRules,
+        // may veto votes, besides the moderators
+        guardian: Option<AccountId>,
         votes_length: u64,
         votes: StorageHashMap<VoteId, Vote>,
-        voters: StorageHashMap<(VoteId, AccountId), ChoiceId>,
-        choices: StorageVec<Choice>,
-        choices_num: u32,
+        // vote ids of an index by their position
+        index_entries: StorageHashMap<(VoteIndex, u64), VoteId>,
+        index_lengths: StorageHashMap<VoteIndex, u64>,
+        // position of a vote in an index holding it
+        index_positions: StorageHashMap<(VoteIndex, VoteId), u64>,
+        // position in the live index the next sweep starts from
+        sweep_cursor: u64,
+        // latest ballot of a voter and its weight
+        voters: StorageHashMap<(VoteId, AccountId), (Ballot, u64)>,
+        vote_voters: StorageHashMap<(VoteId, u32), AccountId>,
+        choices: StorageHashMap<(VoteId, ChoiceId), Choice>,
+        // votes a voter bought for a choice of a quadratic vote
+        quadratic_votes: StorageHashMap<(VoteId, AccountId, ChoiceId), u64>,
+        // delegate of an account, globally (none) or for the votes with a tag
+        delegations: StorageHashMap<(AccountId, Option<String>), AccountId>,
+        // accounts delegating to an account, globally or for a tag
+        delegators: StorageHashMap<(AccountId, Option<String>), Vec<AccountId>>,
+        // tags an account delegates for
+        delegation_tags: StorageHashMap<AccountId, Vec<String>>,
+        // delegations to an account, for any tag
+        delegator_counts: StorageHashMap<AccountId, u32>,
+        // active votes, other than quadratic ones, an account cast a ballot in
+        voter_votes: StorageHashMap<AccountId, Vec<VoteId>>,
+        // ballot of the voter carrying the weight of an account which didn't vote itself, and the weight
+        carried: StorageHashMap<(VoteId, AccountId), (AccountId, u64)>,
+        // hash of a secret ballot and its salt, until it is revealed
+        commitments: StorageHashMap<(VoteId, AccountId), Hash>,
     }
 
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(::scale_info::TypeInfo))]
+    pub enum Error {
+        VoteNotExist,
+        InvalidChoice,
+        /// Returned if the caller is not a member of the org or lacks the auth permission.
+        NotEligible,
+        /// Returned if the caller holds no tokens at the vote's snapshot.
+        NoVotingPower,
+        /// Returned if a ratio of the threshold is above 100%.
+        InvalidThreshold,
+        /// Returned if a participation threshold is set but the electorate can't be counted,
+        /// that is the vote is neither token weighted nor restricted to org members.
+        UnknownElectorate,
+        /// Returned if a quadratic vote has no token to pay the votes with.
+        TokenRequired,
+        /// Returned if the vote module may not take snapshots of the token, it has to be set as
+        /// the token's snapshotter.
+        SnapshotFailed,
+        /// Returned if a vote dispatching a call isn't restricted to the org members, isn't weighted
+        /// by the token of the action rules or has a lower threshold than them.
+        ActionRulesNotMet,
+        /// Returned if a secret ballot has no time to reveal the ballots in.
+        InvalidRevealTime,
+        /// Returned if the start delay, vote time and reveal time overflow the timestamp.
+        TimeOverflow,
+        /// Returned if the ballot's shape doesn't match the vote's tally method.
+        BallotMismatch,
+        /// Returned if the ballot is empty, repeats a choice or buys no votes.
+        InvalidBallot,
+        /// Returned if the voter couldn't pay for quadratic votes, the vote module must be
+        /// approved to spend the tokens.
+        PaymentFailed,
+        /// Returned if quadratic votes are bought while the org has no vault to pay them to.
+        VaultNotSet,
+        SelfDelegation,
+        /// Returned if the delegate delegates back to the caller, directly or through others.
+        DelegationCycle,
+        /// Returned if the delegate already has the most delegations an account may have.
+        TooManyDelegators,
+        /// Returned if the caller already delegates for the most tags an account may.
+        TooManyDelegationTags,
+        DelegationNotExist,
+        /// Returned if the ballot is cast, committed or revealed outside of the matching phase.
+        WrongPhase,
+        CommitmentNotExist,
+        /// Returned if the revealed ballot and salt don't hash to the commitment.
+        CommitmentMismatch,
+        /// Returned if the vote's state doesn't allow the operation.
+        InvalidState,
+        /// Returned if the caller is not the proposer of the vote.
+        NotProposer,
+        /// Returned if the caller is neither a moderator of the org nor the guardian.
+        NotGuardian,
+        /// Returned if the caller is not the vote module itself, the change has to be made by an
+        /// executed vote.
+        NotVoteModule,
+    }
+
+    /// Type alias for the contract's result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
     #[ink(event)]
     pub struct StartVote {
         #[ink(topic)]
@@ -128,295 +545,2357 @@ mod vote_manager {
         #[ink(topic)]
         voter: AccountId,
 
-        support_choice: ChoiceId,
+        ballot: Ballot,
+
+        weight: u64,
+    }
+
+    #[ink(event)]
+    pub struct CommitVote {
+        #[ink(topic)]
+        vote_id: VoteId,
+
+        #[ink(topic)]
+        voter: AccountId,
     }
 
     #[ink(event)]
     pub struct ExecuteVote {
         #[ink(topic)]
         vote_id: VoteId,
+
+        action_result: Option<bool>,
+    }
+
+    #[ink(event)]
+    pub struct CancelVote {
+        #[ink(topic)]
+        vote_id: VoteId,
+    }
+
+    #[ink(event)]
+    pub struct VetoVote {
+        #[ink(topic)]
+        vote_id: VoteId,
+
+        #[ink(topic)]
+        vetoer: AccountId,
+
+        reason: String,
+    }
+
+    #[ink(event)]
+    pub struct Delegate {
+        #[ink(topic)]
+        delegator: AccountId,
+
+        #[ink(topic)]
+        delegate: AccountId,
+
+        tag: Option<String>,
+    }
+
+    #[ink(event)]
+    pub struct Undelegate {
+        #[ink(topic)]
+        delegator: AccountId,
+
+        #[ink(topic)]
+        delegate: AccountId,
+
+        tag: Option<String>,
     }
 
     impl VoteManager {
 
         #[ink(constructor)]
-        pub fn new() -> Self {
+        pub fn new(org_contract_address: AccountId, guardian: Option<AccountId>, action_rules: ActionRules) -> Self {
+            assert!(action_rules.min_participation_bps <= BPS_BASE && action_rules.min_approval_bps <= BPS_BASE);
             Self { 
+                org: ink_env::call::FromAccountId::from_account_id(org_contract_address),
+                org_address: org_contract_address,
+                action_rules,
+                guardian,
                 votes_length: 0,
                 votes: StorageHashMap::default(),
+                index_entries: StorageHashMap::default(),
+                index_lengths: StorageHashMap::default(),
+                index_positions: StorageHashMap::default(),
+                sweep_cursor: 0,
                 voters: StorageHashMap::default(),
-                choices: StorageVec::default(),
-                choices_num: 0,
+                vote_voters: StorageHashMap::default(),
+                choices: StorageHashMap::default(),
+                quadratic_votes: StorageHashMap::default(),
+                delegations: StorageHashMap::default(),
+                delegators: StorageHashMap::default(),
+                delegation_tags: StorageHashMap::default(),
+                delegator_counts: StorageHashMap::default(),
+                voter_votes: StorageHashMap::default(),
+                carried: StorageHashMap::default(),
+                commitments: StorageHashMap::default(),
             }
         }
 
         #[ink(message)]
-        pub fn new_vote(&mut self, title: String, desc: String, vote_time: u64, support_require_num: u64, min_require_num: u64, choices: String) -> u64 {
+        pub fn new_vote(&mut self, title: String, desc: String, start_delay: u64, vote_time: u64, reveal_time: Option<u64>, threshold: Threshold, choices: Vec<String>, tally_method: TallyMethod, erc20_address: Option<AccountId>, action: Option<VoteAction>, eligibility: Option<Eligibility>, tag: Option<String>) -> Result<VoteId> {
+            if threshold.min_participation_bps > BPS_BASE || threshold.min_approval_bps > BPS_BASE {
+                return Err(Error::InvalidThreshold);
+            }
+            if tally_method == TallyMethod::Quadratic && erc20_address.is_none() {
+                return Err(Error::TokenRequired);
+            }
+            if reveal_time == Some(0) {
+                return Err(Error::InvalidRevealTime);
+            }
+            if action.is_some() {
+                self.check_action_rules(self.env().caller(), &threshold, erc20_address, &eligibility)?;
+            }
+            // the end of the vote time and of the reveal time follow from the start date
+            let start_date = self.env().block_timestamp().checked_add(start_delay).ok_or(Error::TimeOverflow)?;
+            start_date
+                .checked_add(vote_time)
+                .and_then(|end| end.checked_add(reveal_time.unwrap_or(0)))
+                .ok_or(Error::TimeOverflow)?;
+            let (snapshot_id, electorate) = match erc20_address {
+                Some(address) => self.token_snapshot(address).ok_or(Error::SnapshotFailed)?,
+                None => match &eligibility {
+                    Some(Eligibility::OrgMember(org_address)) => (0, self.member_count(*org_address)),
+                    _ => (0, 0),
+                },
+            };
+            if threshold.min_participation_bps > 0 && electorate == 0 {
+                return Err(Error::UnknownElectorate);
+            }
             let vote_id = self.votes_length.clone();
             self.votes_length += 1;
-            let start_date: u64 = self.env().block_timestamp();
-            let vec: Vec<&str> = choices.split("|").collect();
             let vote = Vote{
                 vote_id: vote_id,
-                executed: false,
+                settled: None,
+                proposer: self.env().caller(),
                 title,
                 desc,
                 start_date: start_date,
                 vote_time,
-                support_require_num,
-                min_require_num,
+                reveal_time,
+                threshold,
+                electorate,
+                tally_method,
                 support_num: 0,
-                choice_index_lo: self.choices_num,
-                choice_index_ho: self.choices_num + vec.len() as u32,
+                voter_num: 0,
+                choice_num: choices.len() as u32,
+                erc20_address,
+                snapshot_id,
+                action,
+                action_result: None,
+                eligibility,
+                tag,
+                veto_reason: None,
             };
-            self.choices_num += vec.len() as u32;
-            let mut index = 0;
-            for choice_content in vec.iter() {
-                self.choices.push(Choice{
-                    choice_id: index,
-                    content: String::from(*choice_content),
+            for (index, content) in choices.into_iter().enumerate() {
+                let choice_id = index as ChoiceId;
+                self.choices.insert((vote_id, choice_id), Choice{
+                    choice_id,
+                    content,
                     yea: 0,
                 });
-                index += 1;
             }
             self.votes.insert(vote_id, vote);
+            self.sweep_finished();
+            self.index_push(VoteIndex::Live, vote_id);
+            self.index_push(VoteIndex::Proposer(self.env().caller()), vote_id);
             self.env().emit_event(StartVote{
                 vote_id,
                 creator: self.env().caller(),
             });
-            vote_id
+            Ok(vote_id)
         }
 
         #[ink(message)]
-        pub fn execute(&mut self, vote_id: VoteId) {
-            assert!(self.vote_exists(vote_id));
-            let vote = self.votes.get(&vote_id).unwrap(); 
-            if self.can_execute(&vote) {
-                let mut vote = self.votes.get_mut(&vote_id).unwrap(); 
-                vote.executed = true;
-                self.env().emit_event(ExecuteVote{
-                    vote_id,
-                });
+        pub fn execute(&mut self, vote_id: VoteId) -> Result<()> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            if self.state(vote) != VoteState::Succeeded {
+                return Err(Error::InvalidState);
             }
-            // true
+            // mark executed before dispatching the call
+            self.settle(vote_id, VoteState::Executed);
+            self.sweep_finished();
+            let action = self.votes.get(&vote_id).unwrap().action.clone();
+            let action_result = action.map(|action| self.dispatch(&action));
+            self.votes.get_mut(&vote_id).unwrap().action_result = action_result;
+            self.env().emit_event(ExecuteVote{
+                vote_id,
+                action_result,
+            });
+            Ok(())
         }
 
+        /// cancel a pending or active vote, only by its proposer, tokens paid for quadratic votes
+        /// are not refunded
         #[ink(message)]
-        pub fn vote(&mut self, vote_id: VoteId, support_choice: u32, voter: AccountId) -> bool {
-            if !self.vote_exists(vote_id) {
-                return false;
+        pub fn cancel(&mut self, vote_id: VoteId) -> Result<()> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            if vote.proposer != self.env().caller() {
+                return Err(Error::NotProposer);
             }
-            if let Some(vote) = self.votes.get_mut(&vote_id) {
-                if support_choice > vote.choice_index_ho - vote.choice_index_lo {
-                    return false;
-                }
-                // has voted
-                if let Some(choice_id) = self.voters.get(&(vote_id, voter)) {
-                    if *choice_id != support_choice {
-                        let choice_vec_index = vote.choice_index_lo + *choice_id;
-                        let choices = &mut self.choices;
-                        choices.get_mut(choice_vec_index).unwrap().yea -= 1;
-                        vote.support_num -= 1;
-                    }
-                } 
-                let choices = &mut self.choices;
-                let choice_vec_index = vote.choice_index_lo + support_choice;
-                let voter_choice = choices.get_mut(choice_vec_index).unwrap();
-                voter_choice.yea += 1;
-                // record voter choice id
-                self.voters.insert((vote_id, voter), support_choice);    
-                vote.support_num += 1;
-                self.env().emit_event(CastVote{
-                    vote_id,
-                    voter: self.env().caller(), 
-                    support_choice,
-                });
-                true
-            } else {
-                false
+            match self.state(vote) {
+                VoteState::Pending | VoteState::Active => {}
+                _ => return Err(Error::InvalidState),
             }
+            self.settle(vote_id, VoteState::Cancelled);
+            self.env().emit_event(CancelVote{
+                vote_id,
+            });
+            Ok(())
         }
 
+        /// veto a vote before it is executed, only by a moderator of the org or the guardian, tokens
+        /// paid for quadratic votes are not refunded
         #[ink(message)]
-        pub fn query_one_vote(&self, vote_id: VoteId) -> DisplayVote {
-            assert!(self.vote_exists(vote_id));
-            let vote = self.votes.get(&vote_id).unwrap(); 
-            let display_vote = self.convert_vote_to_displayvote(&vote); 
-            display_vote
+        pub fn veto(&mut self, vote_id: VoteId, reason: String) -> Result<()> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            let vetoer = self.env().caller();
+            if self.guardian != Some(vetoer) && !self.is_moderator(vetoer) {
+                return Err(Error::NotGuardian);
+            }
+            match self.state(vote) {
+                VoteState::Pending | VoteState::Active | VoteState::Succeeded => {}
+                _ => return Err(Error::InvalidState),
+            }
+            self.settle(vote_id, VoteState::Vetoed);
+            self.votes.get_mut(&vote_id).unwrap().veto_reason = Some(reason.clone());
+            self.env().emit_event(VetoVote{
+                vote_id,
+                vetoer,
+                reason,
+            });
+            Ok(())
         }
 
+        /// replace the guardian, only by the guardian or by the vote module itself through
+        /// an executed vote
         #[ink(message)]
-        pub fn query_all_vote(&self) -> alloc::vec::Vec<DisplayVote> {
-            let mut v: alloc::vec::Vec<DisplayVote> = alloc::vec::Vec::new();
-            for (_, vote) in &self.votes {
-                let vote = self.convert_vote_to_displayvote(&vote);
-                v.push(vote);
+        pub fn set_guardian(&mut self, guardian: Option<AccountId>) -> Result<()> {
+            let caller = self.env().caller();
+            if self.guardian != Some(caller) && caller != self.env().account_id() {
+                return Err(Error::NotGuardian);
             }
-            return v;
+            self.guardian = guardian;
+            Ok(())
         }
 
         #[ink(message)]
-        pub fn query_executed_vote(&self) -> alloc::vec::Vec<DisplayVote> {
-            let mut v: alloc::vec::Vec<DisplayVote> = alloc::vec::Vec::new();
-            for (_, val) in &self.votes {
-                if self.is_vote_executed(&val) {
-                    let vote = self.convert_vote_to_displayvote(&val);
-                    v.push(vote);
-                }
-            }
-            return v;
+        pub fn query_guardian(&self) -> Option<AccountId> {
+            self.guardian
         }
 
+        /// replace the rules of the votes dispatching a call, only by the vote module itself
+        /// through an executed vote
         #[ink(message)]
-        pub fn query_open_vote(&self) -> alloc::vec::Vec<DisplayVote> {
-            let mut v: alloc::vec::Vec<DisplayVote> = alloc::vec::Vec::new();
-            for (_, val) in &self.votes {
-                if self.is_vote_open(&val) {
-                    let vote = self.convert_vote_to_displayvote(&val);
-                    v.push(vote);
-                }
+        pub fn set_action_rules(&mut self, action_rules: ActionRules) -> Result<()> {
+            if self.env().caller() != self.env().account_id() {
+                return Err(Error::NotVoteModule);
+            }
+            if action_rules.min_participation_bps > BPS_BASE || action_rules.min_approval_bps > BPS_BASE {
+                return Err(Error::InvalidThreshold);
             }
-            return v;
+            self.action_rules = action_rules;
+            Ok(())
         }
 
         #[ink(message)]
-        pub fn query_wait_vote(&self) -> alloc::vec::Vec<DisplayVote> {
-            let mut v: alloc::vec::Vec<DisplayVote> = alloc::vec::Vec::new();
-            for (_, val) in &self.votes {
-                if self.is_vote_wait(&val) {
-                    let vote = self.convert_vote_to_displayvote(&val);
-                    v.push(vote);
-                }
-            }
-            return v;
-        }
- 
-        fn convert_vote_to_displayvote(&self, vote: &Vote) -> DisplayVote {
-            let mut choices = Vec::new();
-            let mut index = 0;
-            let source_choices = &self.choices;
-            for choice in source_choices.iter() {
-                if index >= vote.choice_index_lo && index < vote.choice_index_ho {
-                    let s = format!("{0}:{1}", choice.content.clone(), choice.yea);
-                    choices.push(s);
-                }
-                index += 1;
-            }
-            let choices_content = choices.join("|"); 
-            let vote = DisplayVote{
-                vote_id: vote.vote_id,
-                executed: vote.executed,
-                title: vote.title.clone(),
-                desc: vote.desc.clone(),
-                start_date: vote.start_date,
-                vote_time: vote.vote_time,
-                support_require_num: vote.support_require_num,
-                min_require_num: vote.min_require_num,
-                support_num: vote.support_num,
-                choices: choices_content,
-            };
-            vote
+        pub fn query_action_rules(&self) -> ActionRules {
+            self.action_rules
         }
 
-        fn vote_exists(&self, vote_id: u64) -> bool {
-            return vote_id < self.votes_length;
+        #[ink(message)]
+        pub fn vote(&mut self, vote_id: VoteId, support_choice: u32) -> Result<()> {
+            self.cast_ballot(vote_id, Ballot::Single(support_choice))
         }
 
-        fn is_vote_open(&self, vote: &Vote) -> bool {
-            return self.env().block_timestamp() < vote.start_date + vote.vote_time && !vote.executed;
+        #[ink(message)]
+        pub fn cast_ballot(&mut self, vote_id: VoteId, ballot: Ballot) -> Result<()> {
+            self.check_phase(vote_id, VotePhase::Open)?;
+            self.check_ballot(vote_id, &ballot)?;
+            let voter = self.env().caller();
+            if !self.is_eligible(vote_id, voter) {
+                return Err(Error::NotEligible);
+            }
+            self.record_ballot(vote_id, voter, ballot)
         }
 
-        fn is_vote_wait(&self, vote: &Vote) -> bool {
-            return self.env().block_timestamp() > vote.start_date + vote.vote_time && !vote.executed;
+        /// commit the hash of a secret ballot and a salt, see `compute_commitment`,
+        /// replacing the previous commitment
+        #[ink(message)]
+        pub fn commit_ballot(&mut self, vote_id: VoteId, commitment: Hash) -> Result<()> {
+            self.check_phase(vote_id, VotePhase::Commit)?;
+            let voter = self.env().caller();
+            if !self.is_eligible(vote_id, voter) {
+                return Err(Error::NotEligible);
+            }
+            self.commitments.insert((vote_id, voter), commitment);
+            self.env().emit_event(CommitVote{
+                vote_id,
+                voter,
+            });
+            Ok(())
         }
 
-        fn is_vote_executed(&self, vote: &Vote) -> bool {
-            return vote.executed;
+        /// reveal the caller's committed ballot, which is then counted
+        #[ink(message)]
+        pub fn reveal_ballot(&mut self, vote_id: VoteId, ballot: Ballot, salt: Hash) -> Result<()> {
+            self.check_phase(vote_id, VotePhase::Reveal)?;
+            let voter = self.env().caller();
+            let commitment = match self.commitments.get(&(vote_id, voter)) {
+                Some(commitment) => *commitment,
+                None => return Err(Error::CommitmentNotExist),
+            };
+            if commitment != self.compute_commitment(ballot.clone(), salt) {
+                return Err(Error::CommitmentMismatch);
+            }
+            self.check_ballot(vote_id, &ballot)?;
+            self.record_ballot(vote_id, voter, ballot)?;
+            self.commitments.take(&(vote_id, voter));
+            Ok(())
         }
 
-        fn is_vote_finished(&self, vote: &Vote) -> bool {
-            return self.env().block_timestamp() < vote.start_date + vote.vote_time;
+        /// the commitment of a secret ballot, the salt has to be random and kept until the reveal
+        ///
+        /// the ballot is only hidden if this is not called in a transaction
+        #[ink(message)]
+        pub fn compute_commitment(&self, ballot: Ballot, salt: Hash) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(ballot, salt)))
         }
 
-        fn can_execute(&self, vote: &Vote) -> bool {
+        /// count the ballot and record it as the voter's
+        fn record_ballot(&mut self, vote_id: VoteId, voter: AccountId, ballot: Ballot) -> Result<()> {
+            let previous = self.voters.get(&(vote_id, voter)).cloned();
+            let first_ballot = previous.is_none();
+            let weight = if let Ballot::Quadratic { choice_id, votes } = ballot {
+                self.buy_quadratic_votes(vote_id, voter, choice_id, votes)?;
+                // quadratic votes add up, the weight kept is all the votes bought
+                let bought = previous.map_or(0, |(_, weight)| weight);
+                self.count_ballot(vote_id, &ballot, votes, true);
+                self.voters.insert((vote_id, voter), (ballot.clone(), bought + votes));
+                votes
+            } else {
+                let own_weight = match &previous {
+                    // the weight of the previous ballot already holds the voter's own and delegated weight
+                    Some((_, previous_weight)) => *previous_weight,
+                    None => self.take_own_weight(vote_id, voter),
+                };
+                let weight = own_weight + self.claim_delegated_weight(vote_id, voter);
+                if weight == 0 {
+                    return Err(Error::NoVotingPower);
+                }
+                // has voted, take back the previous ballot first
+                if let Some((previous_ballot, previous_weight)) = previous {
+                    self.count_ballot(vote_id, &previous_ballot, previous_weight, false);
+                }
+                self.count_ballot(vote_id, &ballot, weight, true);
+                self.voters.insert((vote_id, voter), (ballot.clone(), weight));
+                weight
+            };
+            if first_ballot {
+                self.add_voter(vote_id, voter);
+            }
+            self.env().emit_event(CastVote{
+                vote_id,
+                voter,
+                ballot,
+                weight,
+            });
+            Ok(())
+        }
 
-            if vote.executed {
-                return false;
+        /// delegate the caller's voting power to another account, for the votes with the tag
+        /// or globally if no tag, replacing the previous delegation
+        #[ink(message)]
+        pub fn delegate(&mut self, to: AccountId, tag: Option<String>) -> Result<()> {
+            let delegator = self.env().caller();
+            if to == delegator {
+                return Err(Error::SelfDelegation);
             }
-            if self.is_vote_open(&vote) {
-                return false;
+            if self.creates_cycle(delegator, to, &tag) {
+                return Err(Error::DelegationCycle);
             }
-            if vote.support_num < vote.min_require_num {
-                return false;
+            let previous = self.delegations.get(&(delegator, tag.clone())).copied();
+            if previous != Some(to) && *self.delegator_counts.get(&to).unwrap_or(&0) >= MAX_DELEGATORS {
+                return Err(Error::TooManyDelegators);
             }
-            if vote.support_num == 0 {
-                return false;
+            let tag_count = self.delegation_tags.get(&delegator).map_or(0, |tags| tags.len());
+            if tag.is_some() && previous.is_none() && tag_count >= MAX_DELEGATION_TAGS {
+                return Err(Error::TooManyDelegationTags);
             }
-            let mut index = 0;
-            let choices = &self.choices;
-            for choice in choices.iter() {
-                if index >= vote.choice_index_lo && index < vote.choice_index_ho {
-                    if choice.yea >= vote.support_require_num {
-                        return true;
-                    }
+            let mut vote_ids = self.votes_above(delegator);
+            self.remove_delegation(delegator, &tag);
+            self.delegations.insert((delegator, tag.clone()), to);
+            self.delegators.entry((to, tag.clone())).or_insert(Vec::new()).push(delegator);
+            *self.delegator_counts.entry(to).or_insert(0) += 1;
+            if let Some(t) = &tag {
+                self.delegation_tags.entry(delegator).or_insert(Vec::new()).push(t.clone());
+            }
+            for vote_id in self.votes_above(delegator) {
+                if !vote_ids.contains(&vote_id) {
+                    vote_ids.push(vote_id);
                 }
-                index += 1;
             }
-            return false;
+            self.redelegate_weight(delegator, &tag, vote_ids);
+            self.env().emit_event(Delegate{
+                delegator,
+                delegate: to,
+                tag,
+            });
+            Ok(())
         }
-    }
-
-    #[cfg(test)]
-    mod tests {
-        use ink_lang as ink;
-
-        use super::*;
-        use ink_env::{
-            call,
-            test,
-        };
-
-        #[ink::test]
-        fn test_split() {
 
-            let choices = "A,B,C".to_string();
-            let split = choices.split(",");
-            ink_env::debug_println("hello");
-            for s in split {
-                ink_env::debug_println(&s);
+        #[ink(message)]
+        pub fn undelegate(&mut self, tag: Option<String>) -> Result<()> {
+            let delegator = self.env().caller();
+            let vote_ids = self.votes_above(delegator);
+            match self.remove_delegation(delegator, &tag) {
+                Some(delegate) => {
+                    self.redelegate_weight(delegator, &tag, vote_ids);
+                    self.env().emit_event(Undelegate{
+                        delegator,
+                        delegate,
+                        tag,
+                    });
+                    Ok(())
+                }
+                None => Err(Error::DelegationNotExist),
             }
         }
 
-        #[ink::test]
-        fn test_split_with_vec() {
-            let choices = "A,B,C".to_string();
-            let vec: Vec<&str> = choices.split(",").collect();
-            let i:u32 = 1;
-            let length = i + vec.len() as u32;
-            assert!(length == 4);
-            for s in vec{
-                ink_env::debug_println(&s);
-            }
+        #[ink(message)]
+        pub fn query_delegation(&self, delegator: AccountId, tag: Option<String>) -> Option<AccountId> {
+            self.delegations.get(&(delegator, tag)).copied()
         }
 
-        #[ink::test]
-        fn test_calculate() {
-            let choice: u64 = 3;
-            let support: u64 = 5;
-            let t : u64 = choice * 1000 / support; 
-            ink_env::debug_println(t.to_string().as_str());
+        #[ink(message)]
+        pub fn query_delegators(&self, delegate: AccountId, tag: Option<String>) -> Vec<AccountId> {
+            self.delegators.get(&(delegate, tag)).cloned().unwrap_or(Vec::new())
         }
 
-        #[ink::test]
-        fn new_vote_manager() {
-            // let accounts =
+        #[ink(message)]
+        pub fn query_one_vote(&self, vote_id: VoteId) -> Result<DisplayVote> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            let display_vote = self.convert_vote_to_displayvote(&vote); 
+            Ok(display_vote)
+        }
+
+        #[ink(message)]
+        pub fn query_vote_choices(&self, vote_id: VoteId) -> Result<Vec<Choice>> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            Ok(self.vote_choices(&vote))
+        }
+
+        /// query a page of the votes matching the filter, from the cursor on
+        ///
+        /// the page holds at most `limit` votes, capped to MAX_PAGE_SIZE, and is cut short after
+        /// looking at MAX_PAGE_SCAN votes; query the next page from `next_cursor`
+        #[ink(message)]
+        pub fn query_votes(&self, filter: VoteFilter, cursor: Option<u64>, limit: u32) -> VotePage {
+            // look at the smallest indexes holding all the matching votes
+            let indexes = match (filter.state, filter.proposer) {
+                (Some(state @ VoteState::Cancelled), _) | (Some(state @ VoteState::Vetoed), _) | (Some(state @ VoteState::Executed), _) => {
+                    self.state_indexes(state)
+                }
+                (_, Some(proposer)) => vec![VoteIndex::Proposer(proposer)],
+                (Some(state), None) => self.state_indexes(state),
+                (None, None) => vec![VoteIndex::All],
+            };
+            self.page(&indexes, cursor, limit, |vote| self.matches(vote, &filter))
+        }
+
+        /// count the votes in each state, the votes over are counted from the lengths of their
+        /// indexes, only the pending and active votes and those over since they were last swept
+        /// are looked at
+        #[ink(message)]
+        pub fn vote_count_by_state(&self) -> VoteStateCount {
+            let settled_count = |state| self.index_length(&VoteIndex::Settled(state)) as u32;
+            let mut count = VoteStateCount {
+                succeeded: self.index_length(&VoteIndex::Succeeded) as u32,
+                defeated: settled_count(VoteState::Defeated),
+                cancelled: settled_count(VoteState::Cancelled),
+                vetoed: settled_count(VoteState::Vetoed),
+                executed: settled_count(VoteState::Executed),
+                ..Default::default()
+            };
+            for position in 0..self.index_length(&VoteIndex::Live) {
+                let vote_id = self.index_entry(&VoteIndex::Live, position);
+                match self.state(self.votes.get(&vote_id).unwrap()) {
+                    VoteState::Pending => count.pending += 1,
+                    VoteState::Active => count.active += 1,
+                    VoteState::Succeeded => count.succeeded += 1,
+                    _ => count.defeated += 1,
+                }
+            }
+            count
+        }
+
+        /// query a page of all the votes by id, paged as by `query_votes`
+        #[ink(message)]
+        pub fn query_all_vote(&self, cursor: Option<u64>, limit: u32) -> VotePage {
+            self.page(&[VoteIndex::All], cursor, limit, |_| true)
+        }
+
+        /// query a page of the executed votes, paged as by `query_votes`
+        #[ink(message)]
+        pub fn query_executed_vote(&self, cursor: Option<u64>, limit: u32) -> VotePage {
+            self.page(&[VoteIndex::Settled(VoteState::Executed)], cursor, limit, |_| true)
+        }
+
+        /// query a page of the pending and active votes, paged as by `query_votes`
+        #[ink(message)]
+        pub fn query_open_vote(&self, cursor: Option<u64>, limit: u32) -> VotePage {
+            self.page(&[VoteIndex::Live], cursor, limit, |vote| {
+                matches!(self.state(vote), VoteState::Pending | VoteState::Active)
+            })
+        }
+
+        /// query a page of the succeeded and defeated votes, paged as by `query_votes`
+        #[ink(message)]
+        pub fn query_wait_vote(&self, cursor: Option<u64>, limit: u32) -> VotePage {
+            let indexes = [VoteIndex::Settled(VoteState::Defeated), VoteIndex::Succeeded, VoteIndex::Live];
+            self.page(&indexes, cursor, limit, |vote| {
+                matches!(self.state(vote), VoteState::Succeeded | VoteState::Defeated)
+            })
+        }
+
+        /// the page of the votes of the indexes, one after the other, which are kept, from the
+        /// cursor on
+        ///
+        /// the cursor is the position among the votes of all the indexes. The page holds at most
+        /// `limit` votes, capped to MAX_PAGE_SIZE, and is cut short after looking at MAX_PAGE_SCAN
+        /// votes
+        fn page(&self, indexes: &[VoteIndex], cursor: Option<u64>, limit: u32, keep: impl Fn(&Vote) -> bool) -> VotePage {
+            let limit = limit.min(MAX_PAGE_SIZE) as usize;
+            let mut position = cursor.unwrap_or(0);
+            let mut votes = Vec::new();
+            let mut scanned = 0;
+            // position of the first vote of the index
+            let mut offset = 0;
+            for index in indexes {
+                let length = self.index_length(index);
+                while position < offset + length {
+                    if votes.len() == limit || scanned == MAX_PAGE_SCAN {
+                        return VotePage {
+                            votes,
+                            next_cursor: Some(position),
+                        };
+                    }
+                    let vote = self.votes.get(&self.index_entry(index, position - offset)).unwrap();
+                    if keep(vote) {
+                        votes.push(self.convert_vote_to_displayvote(vote));
+                    }
+                    scanned += 1;
+                    position += 1;
+                }
+                offset += length;
+            }
+            VotePage {
+                votes,
+                next_cursor: None,
+            }
+        }
+
+        /// the indexes holding the votes which may be in the state
+        fn state_indexes(&self, state: VoteState) -> Vec<VoteIndex> {
+            match state {
+                VoteState::Pending | VoteState::Active => vec![VoteIndex::Live],
+                VoteState::Succeeded => vec![VoteIndex::Succeeded, VoteIndex::Live],
+                VoteState::Defeated => vec![VoteIndex::Settled(state), VoteIndex::Live],
+                _ => vec![VoteIndex::Settled(state)],
+            }
+        }
+
+        fn index_length(&self, index: &VoteIndex) -> u64 {
+            match index {
+                VoteIndex::All => self.votes_length,
+                _ => *self.index_lengths.get(index).unwrap_or(&0),
+            }
+        }
+
+        fn index_entry(&self, index: &VoteIndex, position: u64) -> VoteId {
+            match index {
+                VoteIndex::All => position,
+                _ => *self.index_entries.get(&(index.clone(), position)).unwrap(),
+            }
+        }
+
+        fn index_push(&mut self, index: VoteIndex, vote_id: VoteId) {
+            let length = self.index_length(&index);
+            self.index_entries.insert((index.clone(), length), vote_id);
+            self.index_positions.insert((index.clone(), vote_id), length);
+            self.index_lengths.insert(index, length + 1);
+        }
+
+        /// take the vote out of the index if it holds it, the last vote of the index takes its
+        /// position
+        fn index_remove(&mut self, index: VoteIndex, vote_id: VoteId) {
+            let position = match self.index_positions.take(&(index.clone(), vote_id)) {
+                Some(position) => position,
+                None => return,
+            };
+            let last = self.index_length(&index) - 1;
+            let last_id = self.index_entries.take(&(index.clone(), last)).unwrap();
+            if position != last {
+                self.index_entries.insert((index.clone(), position), last_id);
+                self.index_positions.insert((index.clone(), last_id), position);
+            }
+            self.index_lengths.insert(index, last);
+        }
+
+        fn matches(&self, vote: &Vote, filter: &VoteFilter) -> bool {
+            if filter.proposer.map_or(false, |proposer| proposer != vote.proposer) {
+                return false;
+            }
+            if filter.start_from.map_or(false, |from| vote.start_date < from) {
+                return false;
+            }
+            if filter.start_to.map_or(false, |to| vote.start_date >= to) {
+                return false;
+            }
+            filter.state.map_or(true, |state| state == self.state(vote))
+        }
+
+        /// settle the vote in a final state and move it to the state's index
+        fn settle(&mut self, vote_id: VoteId, state: VoteState) {
+            self.votes.get_mut(&vote_id).unwrap().settled = Some(state);
+            self.index_remove(VoteIndex::Live, vote_id);
+            self.index_remove(VoteIndex::Succeeded, vote_id);
+            self.index_push(VoteIndex::Settled(state), vote_id);
+        }
+
+        /// take the votes over out of the live ones, the defeated votes are settled and the
+        /// succeeded ones wait in their index to be executed or vetoed
+        ///
+        /// a sweep looks at MAX_SWEEP live votes from where the last one stopped, the votes over
+        /// which aren't swept yet are still found from the live index
+        fn sweep_finished(&mut self) {
+            for _ in 0..MAX_SWEEP {
+                let length = self.index_length(&VoteIndex::Live);
+                if length == 0 {
+                    break;
+                }
+                let mut position = self.sweep_cursor % length;
+                let vote_id = self.index_entry(&VoteIndex::Live, position);
+                // the last live vote takes the position of a vote swept
+                match self.state(self.votes.get(&vote_id).unwrap()) {
+                    VoteState::Defeated => self.settle(vote_id, VoteState::Defeated),
+                    VoteState::Succeeded => {
+                        self.index_remove(VoteIndex::Live, vote_id);
+                        self.index_push(VoteIndex::Succeeded, vote_id);
+                    }
+                    _ => position += 1,
+                }
+                self.sweep_cursor = position;
+            }
+        }
+
+        fn convert_vote_to_displayvote(&self, vote: &Vote) -> DisplayVote {
+            let winner = self.winner(vote);
+            let vote = DisplayVote{
+                vote_id: vote.vote_id,
+                state: self.state(vote),
+                proposer: vote.proposer,
+                title: vote.title.clone(),
+                desc: vote.desc.clone(),
+                start_date: vote.start_date,
+                vote_time: vote.vote_time,
+                reveal_time: vote.reveal_time,
+                phase: self.phase(vote),
+                threshold: vote.threshold,
+                electorate: vote.electorate,
+                tally_method: vote.tally_method,
+                support_num: vote.support_num,
+                voter_num: vote.voter_num,
+                participation_bps: ratio_bps(vote.support_num, vote.electorate),
+                approval_bps: ratio_bps(winner.map_or(0, |(_, votes)| votes), vote.support_num),
+                winner: winner.map(|(choice_id, _)| choice_id),
+                choices: self.vote_choices(vote),
+                erc20_address: vote.erc20_address,
+                snapshot_id: vote.snapshot_id,
+                action: vote.action.clone(),
+                action_result: vote.action_result,
+                eligibility: vote.eligibility.clone(),
+                tag: vote.tag.clone(),
+                veto_reason: vote.veto_reason.clone(),
+            };
+            vote
+        }
+
+        // calls to the org, token and action contracts, off-chain tests call the `mock` module instead
+
+        /// take a snapshot of the token balances, return its id and the total supply at it, none if
+        /// the vote module isn't the token's snapshotter
+        #[cfg(not(test))]
+        fn token_snapshot(&self, token_address: AccountId) -> Option<(u32, u64)> {
+            let mut erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            let snapshot_id = erc20.snapshot()?;
+            Some((snapshot_id, erc20.total_supply_at(snapshot_id)?))
+        }
+
+        #[cfg(not(test))]
+        fn token_balance_at(&self, token_address: AccountId, owner: AccountId, snapshot_id: u32) -> u64 {
+            let erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            erc20.balance_of_at(owner, snapshot_id).unwrap_or(0)
+        }
+
+        /// transfer tokens the owner approved the vote module to spend
+        #[cfg(not(test))]
+        fn token_transfer_from(&self, token_address: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let mut erc20: Erc20 = ink_env::call::FromAccountId::from_account_id(token_address);
+            erc20.transfer_from(from, to, value)
+        }
+
+        #[cfg(not(test))]
+        fn vault_address(&self) -> Option<AccountId> {
+            self.org.get_vault_address()
+        }
+
+        #[cfg(not(test))]
+        fn is_moderator(&self, account: AccountId) -> bool {
+            self.org.is_dao_moderator(account)
+        }
+
+        /// whether the account is a member or moderator of the org
+        #[cfg(not(test))]
+        fn is_member(&self, org_address: AccountId, account: AccountId) -> bool {
+            let org: OrgManager = ink_env::call::FromAccountId::from_account_id(org_address);
+            org.is_dao_member(account) || org.is_dao_moderator(account)
+        }
+
+        #[cfg(not(test))]
+        fn member_count(&self, org_address: AccountId) -> u64 {
+            let org: OrgManager = ink_env::call::FromAccountId::from_account_id(org_address);
+            org.get_dao_member_count()
+        }
+
+        #[cfg(not(test))]
+        fn has_permission(&self, auth_address: AccountId, account: AccountId, contract_name: String, function_name: String) -> bool {
+            let auth: Auth = ink_env::call::FromAccountId::from_account_id(auth_address);
+            auth.has_permission(account, contract_name, function_name)
+        }
+
+        /// dispatch the vote's call, return whether it succeeded
+        #[cfg(not(test))]
+        fn dispatch(&self, action: &VoteAction) -> bool {
+            let call = build_call::<<Self as ::ink_lang::ContractEnv>::Env>()
+                .callee(action.callee)
+                .gas_limit(action.gas_limit)
+                .transferred_value(action.transferred_value)
+                .exec_input(
+                    ExecutionInput::new(action.selector.into()).push_arg(CallInput(&action.input)),
+                );
+            if action.returns_bool {
+                // the callee refused the call if it returned false
+                matches!(call.returns::<ReturnType<bool>>().fire(), Ok(true))
+            } else {
+                call.returns::<()>().fire().is_ok()
+            }
+        }
+
+        #[cfg(test)]
+        fn token_snapshot(&self, token_address: AccountId) -> Option<(u32, u64)> {
+            mock::snapshot(token_address)
+        }
+
+        #[cfg(test)]
+        fn token_balance_at(&self, token_address: AccountId, owner: AccountId, snapshot_id: u32) -> u64 {
+            mock::balance_of_at(token_address, owner, snapshot_id)
+        }
+
+        #[cfg(test)]
+        fn token_transfer_from(&self, token_address: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            mock::transfer_from(token_address, self.env().account_id(), from, to, value)
+        }
+
+        #[cfg(test)]
+        fn vault_address(&self) -> Option<AccountId> {
+            mock::vault_address()
+        }
+
+        #[cfg(test)]
+        fn is_moderator(&self, account: AccountId) -> bool {
+            mock::is_moderator(account)
+        }
+
+        #[cfg(test)]
+        fn is_member(&self, org_address: AccountId, account: AccountId) -> bool {
+            mock::is_member(org_address, account)
+        }
+
+        #[cfg(test)]
+        fn member_count(&self, org_address: AccountId) -> u64 {
+            mock::member_count(org_address)
+        }
+
+        #[cfg(test)]
+        fn has_permission(&self, auth_address: AccountId, account: AccountId, contract_name: String, function_name: String) -> bool {
+            mock::has_permission(auth_address, account, contract_name, function_name)
+        }
+
+        #[cfg(test)]
+        fn dispatch(&self, action: &VoteAction) -> bool {
+            mock::dispatch(self.env().account_id(), action)
+        }
+
+        /// check the vote is active and in the phase
+        fn check_phase(&self, vote_id: VoteId, phase: VotePhase) -> Result<()> {
+            let vote = self.votes.get(&vote_id).ok_or(Error::VoteNotExist)?;
+            if self.state(vote) != VoteState::Active {
+                return Err(Error::InvalidState);
+            }
+            if self.phase(vote) != phase {
+                return Err(Error::WrongPhase);
+            }
+            Ok(())
+        }
+
+        /// check a vote dispatching a call meets the action rules
+        fn check_action_rules(&self, proposer: AccountId, threshold: &Threshold, erc20_address: Option<AccountId>, eligibility: &Option<Eligibility>) -> Result<()> {
+            let rules = self.action_rules;
+            if !self.is_member(self.org_address, proposer) {
+                return Err(Error::NotEligible);
+            }
+            let org_vote = matches!(eligibility, Some(Eligibility::OrgMember(org_address)) if *org_address == self.org_address);
+            let token_vote = erc20_address.is_some() && erc20_address == rules.token;
+            if !org_vote && !token_vote {
+                return Err(Error::ActionRulesNotMet);
+            }
+            if threshold.min_participation_bps < rules.min_participation_bps || threshold.min_approval_bps < rules.min_approval_bps {
+                return Err(Error::ActionRulesNotMet);
+            }
+            Ok(())
+        }
+
+        fn is_eligible(&self, vote_id: VoteId, voter: AccountId) -> bool {
+            let vote = self.votes.get(&vote_id).unwrap();
+            match &vote.eligibility {
+                Some(Eligibility::OrgMember(org_address)) => self.is_member(*org_address, voter),
+                Some(Eligibility::AuthAction { auth_address, contract_name, function_name }) => {
+                    self.has_permission(*auth_address, voter, contract_name.clone(), function_name.clone())
+                }
+                None => true,
+            }
+        }
+
+        /// token balance of the voter at the vote's snapshot, or 1 for unweighted votes
+        fn voting_weight(&self, vote_id: VoteId, voter: AccountId) -> u64 {
+            let vote = self.votes.get(&vote_id).unwrap();
+            match vote.erc20_address {
+                Some(address) => self.token_balance_at(address, voter, vote.snapshot_id),
+                None => 1,
+            }
+        }
+
+        fn check_ballot(&self, vote_id: VoteId, ballot: &Ballot) -> Result<()> {
+            let vote = self.votes.get(&vote_id).unwrap();
+            let choice_ids: &[ChoiceId] = match (vote.tally_method, ballot) {
+                (TallyMethod::Plurality, Ballot::Single(choice_id)) => core::slice::from_ref(choice_id),
+                (TallyMethod::Approval, Ballot::Approval(choice_ids)) => choice_ids,
+                (TallyMethod::RankedChoice, Ballot::Ranked(ranking)) => ranking,
+                (TallyMethod::Quadratic, Ballot::Quadratic { choice_id, votes }) => {
+                    if *votes == 0 {
+                        return Err(Error::InvalidBallot);
+                    }
+                    core::slice::from_ref(choice_id)
+                }
+                _ => return Err(Error::BallotMismatch),
+            };
+            if choice_ids.is_empty() {
+                return Err(Error::InvalidBallot);
+            }
+            for (index, choice_id) in choice_ids.iter().enumerate() {
+                if *choice_id >= vote.choice_num {
+                    return Err(Error::InvalidChoice);
+                }
+                if choice_ids[..index].contains(choice_id) {
+                    return Err(Error::InvalidBallot);
+                }
+            }
+            Ok(())
+        }
+
+        /// add the weight of a ballot to the choices it counts for, or take it back
+        ///
+        /// ranked ballots count for their first preference, the runoff is done by `winner`
+        fn count_ballot(&mut self, vote_id: VoteId, ballot: &Ballot, weight: u64, add: bool) {
+            let choice_ids: &[ChoiceId] = match ballot {
+                Ballot::Single(choice_id) => core::slice::from_ref(choice_id),
+                Ballot::Approval(choice_ids) => choice_ids,
+                Ballot::Ranked(ranking) => &ranking[..1],
+                Ballot::Quadratic { choice_id, .. } => core::slice::from_ref(choice_id),
+            };
+            for choice_id in choice_ids {
+                let choice = self.choices.get_mut(&(vote_id, *choice_id)).unwrap();
+                if add {
+                    choice.yea += weight;
+                } else {
+                    choice.yea -= weight;
+                }
+            }
+            let vote = self.votes.get_mut(&vote_id).unwrap();
+            if add {
+                vote.support_num += weight;
+            } else {
+                vote.support_num -= weight;
+            }
+        }
+
+        /// the voter's own weight, voting directly overrides the delegation so the weight
+        /// is taken back from the delegate's ballot carrying it
+        fn take_own_weight(&mut self, vote_id: VoteId, voter: AccountId) -> u64 {
+            match self.carried.take(&(vote_id, voter)) {
+                Some((carrier, weight)) => {
+                    self.release_carried(vote_id, carrier, weight);
+                    weight
+                }
+                None => self.voting_weight(vote_id, voter),
+            }
+        }
+
+        /// move the weight of the accounts delegating to the voter for this vote, directly or
+        /// through up to `MAX_DELEGATION_DEPTH` delegations, onto the voter's ballot and return the
+        /// weight newly carried
+        ///
+        /// accounts which voted themselves keep their weight and that of their own delegators
+        fn claim_delegated_weight(&mut self, vote_id: VoteId, voter: AccountId) -> u64 {
+            let vote = self.votes.get(&vote_id).unwrap();
+            let tag = vote.tag.clone();
+            let mut claimed = 0;
+            let mut visited = vec![voter];
+            let mut pending = self.delegators_below(voter, &tag, 1);
+            while let Some((account, depth)) = pending.pop() {
+                if visited.contains(&account) || self.voters.contains_key(&(vote_id, account)) {
+                    continue;
+                }
+                visited.push(account);
+                let weight = match self.carried.get(&(vote_id, account)).copied() {
+                    Some((carrier, _)) if carrier == voter => {
+                        pending.extend(self.delegators_below(account, &tag, depth + 1));
+                        continue;
+                    }
+                    Some((carrier, weight)) => {
+                        self.release_carried(vote_id, carrier, weight);
+                        weight
+                    }
+                    None if self.is_eligible(vote_id, account) => self.voting_weight(vote_id, account),
+                    None => 0,
+                };
+                self.carried.insert((vote_id, account), (voter, weight));
+                claimed += weight;
+                pending.extend(self.delegators_below(account, &tag, depth + 1));
+            }
+            claimed
+        }
+
+        /// the effective delegators of `delegate` paired with their depth, none past
+        /// `MAX_DELEGATION_DEPTH`
+        fn delegators_below(&self, delegate: AccountId, tag: &Option<String>, depth: u32) -> Vec<(AccountId, u32)> {
+            if depth > MAX_DELEGATION_DEPTH {
+                return Vec::new();
+            }
+            self.effective_delegators(delegate, tag).into_iter().map(|account| (account, depth)).collect()
+        }
+
+        /// take carried weight off the carrier's ballot
+        fn release_carried(&mut self, vote_id: VoteId, carrier: AccountId, weight: u64) {
+            let (ballot, carrier_weight) = self.voters.get(&(vote_id, carrier)).cloned().unwrap();
+            self.count_ballot(vote_id, &ballot, weight, false);
+            self.voters.insert((vote_id, carrier), (ballot, carrier_weight - weight));
+        }
+
+        /// put carried weight on the carrier's ballot
+        fn add_carried(&mut self, vote_id: VoteId, carrier: AccountId, weight: u64) {
+            let (ballot, carrier_weight) = self.voters.get(&(vote_id, carrier)).cloned().unwrap();
+            self.count_ballot(vote_id, &ballot, weight, true);
+            self.voters.insert((vote_id, carrier), (ballot, carrier_weight + weight));
+        }
+
+        /// after the delegation of `account` for the tag changed, move the weight of the account
+        /// and of the accounts delegating to it on the votes being counted, unless it voted itself
+        ///
+        /// the weight is taken off the ballot carrying it and put on the ballot of the first
+        /// delegate down the new delegation chain which voted, if any. Only the votes of
+        /// `vote_ids`, those the delegates of the old and new chains voted in, can carry it
+        fn redelegate_weight(&mut self, account: AccountId, tag: &Option<String>, vote_ids: Vec<VoteId>) {
+            let vote_ids: Vec<VoteId> = vote_ids
+                .into_iter()
+                .filter(|vote_id| {
+                    let vote = self.votes.get(vote_id).unwrap();
+                    // a global delegation applies to the votes of any tag
+                    (tag.is_none() || vote.tag == *tag)
+                        && vote.tally_method != TallyMethod::Quadratic
+                        && self.state(vote) == VoteState::Active
+                        && !self.voters.contains_key(&(*vote_id, account))
+                })
+                .collect();
+            for vote_id in vote_ids {
+                let vote_tag = self.votes.get(&vote_id).unwrap().tag.clone();
+                let mut visited = Vec::new();
+                // the carrier is at least one delegation above the account, so the weight it
+                // carries is less than `MAX_DELEGATION_DEPTH` delegations below the account
+                let mut pending = vec![(account, 1)];
+                while let Some((next, depth)) = pending.pop() {
+                    if visited.contains(&next) || self.voters.contains_key(&(vote_id, next)) {
+                        continue;
+                    }
+                    visited.push(next);
+                    if let Some((carrier, weight)) = self.carried.take(&(vote_id, next)) {
+                        self.release_carried(vote_id, carrier, weight);
+                    }
+                    pending.extend(self.delegators_below(next, &vote_tag, depth + 1));
+                }
+                if let Some(carrier) = self.voting_delegate(vote_id, account, &vote_tag) {
+                    let claimed = self.claim_delegated_weight(vote_id, carrier);
+                    self.add_carried(vote_id, carrier, claimed);
+                }
+            }
+        }
+
+        /// the first account down the delegation chain of `account`, at most
+        /// `MAX_DELEGATION_DEPTH` delegations away, which voted on the vote
+        fn voting_delegate(&self, vote_id: VoteId, account: AccountId, tag: &Option<String>) -> Option<AccountId> {
+            let mut current = account;
+            for _ in 0..MAX_DELEGATION_DEPTH {
+                current = self.effective_delegate(current, tag)?;
+                if self.voters.contains_key(&(vote_id, current)) {
+                    return Some(current);
+                }
+            }
+            None
+        }
+
+        /// the active votes which the accounts up to `MAX_DELEGATION_DEPTH` delegations above
+        /// `account`, for any tag, voted in
+        fn votes_above(&self, account: AccountId) -> Vec<VoteId> {
+            let mut vote_ids: Vec<VoteId> = Vec::new();
+            let mut pending = vec![(account, 0)];
+            while let Some((next, depth)) = pending.pop() {
+                if depth > 0 {
+                    for vote_id in self.voter_votes.get(&next).cloned().unwrap_or(Vec::new()) {
+                        if !vote_ids.contains(&vote_id) {
+                            vote_ids.push(vote_id);
+                        }
+                    }
+                }
+                if depth == MAX_DELEGATION_DEPTH {
+                    continue;
+                }
+                if let Some(delegate) = self.delegations.get(&(next, None)) {
+                    pending.push((*delegate, depth + 1));
+                }
+                for t in self.delegation_tags.get(&next).cloned().unwrap_or(Vec::new()) {
+                    pending.push((*self.delegations.get(&(next, Some(t))).unwrap(), depth + 1));
+                }
+            }
+            vote_ids
+        }
+
+        /// the delegate of an account for votes with the tag, the global one if there is no
+        /// delegation for the tag
+        fn effective_delegate(&self, account: AccountId, tag: &Option<String>) -> Option<AccountId> {
+            if tag.is_some() {
+                if let Some(delegate) = self.delegations.get(&(account, tag.clone())) {
+                    return Some(*delegate);
+                }
+            }
+            self.delegations.get(&(account, None)).copied()
+        }
+
+        /// the accounts whose effective delegate for votes with the tag is `delegate`
+        fn effective_delegators(&self, delegate: AccountId, tag: &Option<String>) -> Vec<AccountId> {
+            let mut accounts: Vec<AccountId> = self.query_delegators(delegate, None)
+                .into_iter()
+                .filter(|account| tag.is_none() || !self.delegations.contains_key(&(*account, tag.clone())))
+                .collect();
+            if tag.is_some() {
+                accounts.extend(self.query_delegators(delegate, tag.clone()));
+            }
+            accounts
+        }
+
+        /// whether delegating from `delegator` to `delegate` closes a cycle, a global delegation
+        /// applies to the votes of every tag the delegator has no delegation for
+        fn creates_cycle(&self, delegator: AccountId, delegate: AccountId, tag: &Option<String>) -> bool {
+            let mut tags = vec![tag.clone()];
+            if tag.is_none() {
+                for t in self.reachable_tags(delegate) {
+                    if !self.delegations.contains_key(&(delegator, Some(t.clone()))) {
+                        tags.push(Some(t));
+                    }
+                }
+            }
+            tags.iter().any(|tag| {
+                let mut current = delegate;
+                let mut visited = Vec::new();
+                while !visited.contains(&current) {
+                    if current == delegator {
+                        return true;
+                    }
+                    visited.push(current);
+                    match self.effective_delegate(current, tag) {
+                        Some(next) => current = next,
+                        None => break,
+                    }
+                }
+                false
+            })
+        }
+
+        /// the tags delegated for by the accounts reachable from `from` through any delegation
+        fn reachable_tags(&self, from: AccountId) -> Vec<String> {
+            let mut tags: Vec<String> = Vec::new();
+            let mut visited = Vec::new();
+            let mut pending = vec![from];
+            while let Some(account) = pending.pop() {
+                if visited.contains(&account) {
+                    continue;
+                }
+                visited.push(account);
+                if let Some(delegate) = self.delegations.get(&(account, None)) {
+                    pending.push(*delegate);
+                }
+                for t in self.delegation_tags.get(&account).cloned().unwrap_or(Vec::new()) {
+                    pending.push(*self.delegations.get(&(account, Some(t.clone()))).unwrap());
+                    if !tags.contains(&t) {
+                        tags.push(t);
+                    }
+                }
+            }
+            tags
+        }
+
+        /// remove the delegation of `delegator` for the tag, return the delegate
+        fn remove_delegation(&mut self, delegator: AccountId, tag: &Option<String>) -> Option<AccountId> {
+            let delegate = self.delegations.take(&(delegator, tag.clone()))?;
+            if let Some(delegators) = self.delegators.get_mut(&(delegate, tag.clone())) {
+                delegators.retain(|account| *account != delegator);
+            }
+            if let Some(count) = self.delegator_counts.get_mut(&delegate) {
+                *count -= 1;
+            }
+            if let Some(t) = tag {
+                if let Some(tags) = self.delegation_tags.get_mut(&delegator) {
+                    tags.retain(|delegated_tag| delegated_tag != t);
+                }
+            }
+            Some(delegate)
+        }
+
+        fn add_voter(&mut self, vote_id: VoteId, voter: AccountId) {
+            let vote = self.votes.get_mut(&vote_id).unwrap();
+            let index = vote.voter_num;
+            vote.voter_num += 1;
+            let tally_method = vote.tally_method;
+            self.vote_voters.insert((vote_id, index), voter);
+            // delegations only move the weight of votes other than quadratic ones
+            if tally_method != TallyMethod::Quadratic {
+                let mut vote_ids = self.voter_votes.get(&voter).cloned().unwrap_or(Vec::new());
+                vote_ids.retain(|vote_id| self.state(self.votes.get(vote_id).unwrap()) == VoteState::Active);
+                vote_ids.push(vote_id);
+                self.voter_votes.insert(voter, vote_ids);
+            }
+        }
+
+        /// charge the voter (n + votes)² - n² tokens, n being the votes already bought for the choice,
+        /// paid to the vault of the org
+        ///
+        /// payments are final, they aren't refunded when the vote is cancelled or vetoed
+        fn buy_quadratic_votes(&mut self, vote_id: VoteId, voter: AccountId, choice_id: ChoiceId, votes: u64) -> Result<()> {
+            let bought = *self.quadratic_votes.get(&(vote_id, voter, choice_id)).unwrap_or(&0);
+            let total = bought as u128 + votes as u128;
+            let cost = total * total - bought as u128 * bought as u128;
+            if cost > u64::MAX as u128 {
+                return Err(Error::InvalidBallot);
+            }
+            let vault_address = self.vault_address().ok_or(Error::VaultNotSet)?;
+            let erc20_address = self.votes.get(&vote_id).unwrap().erc20_address.unwrap();
+            if !self.token_transfer_from(erc20_address, voter, vault_address, cost as u64) {
+                return Err(Error::PaymentFailed);
+            }
+            self.quadratic_votes.insert((vote_id, voter, choice_id), total as u64);
+            Ok(())
+        }
+
+        /// the winning choice and its votes, none if nobody voted or the leading choices are tied
+        fn winner(&self, vote: &Vote) -> Option<(ChoiceId, u64)> {
+            if vote.tally_method == TallyMethod::RankedChoice {
+                let rankings: Vec<(&[ChoiceId], u64)> = (0..vote.voter_num)
+                    .filter_map(|index| {
+                        let voter = self.vote_voters.get(&(vote.vote_id, index))?;
+                        match self.voters.get(&(vote.vote_id, *voter)) {
+                            Some((Ballot::Ranked(ranking), weight)) => Some((ranking.as_slice(), *weight)),
+                            _ => None,
+                        }
+                    })
+                    .collect();
+                return instant_runoff(vote.choice_num, &rankings);
+            }
+            let yeas: Vec<u64> = (0..vote.choice_num)
+                .map(|choice_id| self.choices.get(&(vote.vote_id, choice_id)).unwrap().yea)
+                .collect();
+            plurality_winner(&yeas)
+        }
+
+        fn vote_choices(&self, vote: &Vote) -> Vec<Choice> {
+            (0..vote.choice_num)
+                .map(|choice_id| self.choices.get(&(vote.vote_id, choice_id)).unwrap().clone())
+                .collect()
+        }
+
+        fn phase(&self, vote: &Vote) -> VotePhase {
+            let now = self.env().block_timestamp();
+            // `new_vote` checked the times don't overflow
+            let end = vote.start_date.saturating_add(vote.vote_time);
+            match vote.reveal_time {
+                None if now < end => VotePhase::Open,
+                Some(_) if now < end => VotePhase::Commit,
+                Some(reveal_time) if now < end.saturating_add(reveal_time) => VotePhase::Reveal,
+                _ => VotePhase::Closed,
+            }
+        }
+
+        fn state(&self, vote: &Vote) -> VoteState {
+            if let Some(state) = vote.settled {
+                return state;
+            }
+            if self.env().block_timestamp() < vote.start_date {
+                return VoteState::Pending;
+            }
+            if self.phase(vote) != VotePhase::Closed {
+                return VoteState::Active;
+            }
+            if self.passed(vote) {
+                VoteState::Succeeded
+            } else {
+                VoteState::Defeated
+            }
+        }
+
+        /// whether the tallies meet the vote's threshold
+        fn passed(&self, vote: &Vote) -> bool {
+            let threshold = &vote.threshold;
+            if vote.support_num < threshold.min_require_num {
+                return false;
+            }
+            if vote.support_num == 0 {
+                return false;
+            }
+            if ratio_bps(vote.support_num, vote.electorate) < threshold.min_participation_bps {
+                return false;
+            }
+            let winner_votes = match self.winner(vote) {
+                Some((_, votes)) => votes,
+                None => return false,
+            };
+            if winner_votes < threshold.support_require_num {
+                return false;
+            }
+            ratio_bps(winner_votes, vote.support_num) >= threshold.min_approval_bps
+        }
+    }
+
+    /// the choice with the most votes, none if there are no votes or a tie
+    fn plurality_winner(yeas: &[u64]) -> Option<(ChoiceId, u64)> {
+        let max = yeas.iter().copied().max().unwrap_or(0);
+        if max == 0 || yeas.iter().filter(|yea| **yea == max).count() > 1 {
+            return None;
+        }
+        let choice_id = yeas.iter().position(|yea| *yea == max).unwrap();
+        Some((choice_id as ChoiceId, max))
+    }
+
+    /// count ranked ballots by instant runoff, return the winner and its votes in the final round
+    ///
+    /// Every round a ballot counts for its most preferred choice still in the race. A choice
+    /// with more than half of the counted votes wins, otherwise the choice with the fewest votes
+    /// is eliminated, the highest choice id among the tied ones. If all the choices still in the
+    /// race have the same votes there is no winner.
+    fn instant_runoff(choice_num: u32, rankings: &[(&[ChoiceId], u64)]) -> Option<(ChoiceId, u64)> {
+        let mut eliminated = vec![false; choice_num as usize];
+        loop {
+            let mut counts = vec![0u64; choice_num as usize];
+            let mut counted: u64 = 0;
+            for (ranking, weight) in rankings {
+                if let Some(choice_id) = ranking.iter().find(|choice_id| !eliminated[**choice_id as usize]) {
+                    counts[*choice_id as usize] += weight;
+                    counted += weight;
+                }
+            }
+            if counted == 0 {
+                return None;
+            }
+            let remaining: Vec<usize> = (0..choice_num as usize).filter(|index| !eliminated[*index]).collect();
+            let mut leader = remaining[0];
+            let mut weakest = remaining[0];
+            for index in remaining.iter().copied() {
+                if counts[index] > counts[leader] {
+                    leader = index;
+                }
+                if counts[index] <= counts[weakest] {
+                    weakest = index;
+                }
+            }
+            if counts[leader] > counted - counts[leader] {
+                return Some((leader as ChoiceId, counts[leader]));
+            }
+            if counts[leader] == counts[weakest] {
+                return None;
+            }
+            eliminated[weakest] = true;
+        }
+    }
+
+    /// `part` as a share of `total` in basis points, rounded down, 0 if `total` is 0
+    fn ratio_bps(part: u64, total: u64) -> u32 {
+        if total == 0 {
+            return 0;
+        }
+        (part as u128 * BPS_BASE as u128 / total as u128) as u32
+    }
+
+    /// State of the org, token and action contracts the vote module calls in off-chain tests.
+    #[cfg(test)]
+    mod mock {
+        use super::*;
+        use std::{
+            cell::RefCell,
+            collections::BTreeMap,
+        };
+
+        struct State {
+            balances: BTreeMap<(AccountId, AccountId), u64>,
+            allowances: BTreeMap<(AccountId, AccountId, AccountId), u64>,
+            // balances of a token at each of its snapshots, the snapshot id is the index
+            snapshots: BTreeMap<AccountId, Vec<BTreeMap<AccountId, u64>>>,
+            // tokens the vote module isn't the snapshotter of
+            snapshots_denied: Vec<AccountId>,
+            vault_address: Option<AccountId>,
+            moderators: Vec<AccountId>,
+            // members of each org
+            members: Vec<(AccountId, AccountId)>,
+            // permissions granted by each auth module, to the account for the contract's function
+            permissions: Vec<(AccountId, AccountId, String, String)>,
+            // calls dispatched, with the calling account
+            dispatched: Vec<(AccountId, VoteAction)>,
+            // what the callees of actions returning a bool return
+            action_returns: bool,
+        }
+
+        impl Default for State {
+            fn default() -> Self {
+                State {
+                    balances: BTreeMap::new(),
+                    allowances: BTreeMap::new(),
+                    snapshots: BTreeMap::new(),
+                    snapshots_denied: Vec::new(),
+                    vault_address: None,
+                    moderators: Vec::new(),
+                    members: Vec::new(),
+                    permissions: Vec::new(),
+                    dispatched: Vec::new(),
+                    action_returns: true,
+                }
+            }
+        }
+
+        thread_local! {
+            static STATE: RefCell<State> = RefCell::new(State::default());
+        }
+
+        pub fn reset() {
+            STATE.with(|state| *state.borrow_mut() = State::default());
+        }
+
+        pub fn set_balance(token_address: AccountId, owner: AccountId, value: u64) {
+            STATE.with(|state| state.borrow_mut().balances.insert((token_address, owner), value));
+        }
+
+        pub fn approve(token_address: AccountId, owner: AccountId, spender: AccountId, value: u64) {
+            STATE.with(|state| state.borrow_mut().allowances.insert((token_address, owner, spender), value));
+        }
+
+        pub fn set_vault_address(vault_address: AccountId) {
+            STATE.with(|state| state.borrow_mut().vault_address = Some(vault_address));
+        }
+
+        pub fn add_moderator(moderator: AccountId) {
+            STATE.with(|state| state.borrow_mut().moderators.push(moderator));
+        }
+
+        pub fn add_member(org_address: AccountId, member: AccountId) {
+            STATE.with(|state| state.borrow_mut().members.push((org_address, member)));
+        }
+
+        pub fn grant_permission(auth_address: AccountId, account: AccountId, contract_name: &str, function_name: &str) {
+            STATE.with(|state| state.borrow_mut().permissions.push((auth_address, account, contract_name.into(), function_name.into())));
+        }
+
+        pub fn balance_of(token_address: AccountId, owner: AccountId) -> u64 {
+            STATE.with(|state| *state.borrow().balances.get(&(token_address, owner)).unwrap_or(&0))
+        }
+
+        pub fn deny_snapshots(token_address: AccountId) {
+            STATE.with(|state| state.borrow_mut().snapshots_denied.push(token_address));
+        }
+
+        pub fn snapshot(token_address: AccountId) -> Option<(u32, u64)> {
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+                if state.snapshots_denied.contains(&token_address) {
+                    return None;
+                }
+                let balances: BTreeMap<AccountId, u64> = state.balances
+                    .iter()
+                    .filter(|((token, _), _)| *token == token_address)
+                    .map(|((_, owner), value)| (*owner, *value))
+                    .collect();
+                let total_supply = balances.values().sum();
+                let snapshots = state.snapshots.entry(token_address).or_insert(Vec::new());
+                snapshots.push(balances);
+                Some(((snapshots.len() - 1) as u32, total_supply))
+            })
+        }
+
+        pub fn balance_of_at(token_address: AccountId, owner: AccountId, snapshot_id: u32) -> u64 {
+            STATE.with(|state| {
+                state.borrow().snapshots[&token_address][snapshot_id as usize].get(&owner).copied().unwrap_or(0)
+            })
+        }
+
+        pub fn transfer_from(token_address: AccountId, spender: AccountId, from: AccountId, to: AccountId, value: u64) -> bool {
+            let allowance = STATE.with(|state| *state.borrow().allowances.get(&(token_address, from, spender)).unwrap_or(&0));
+            let from_balance = balance_of(token_address, from);
+            if allowance < value || from_balance < value {
+                return false;
+            }
+            let to_balance = balance_of(token_address, to);
+            set_balance(token_address, from, from_balance - value);
+            set_balance(token_address, to, to_balance + value);
+            approve(token_address, from, spender, allowance - value);
+            true
+        }
+
+        pub fn vault_address() -> Option<AccountId> {
+            STATE.with(|state| state.borrow().vault_address)
+        }
+
+        pub fn is_moderator(account: AccountId) -> bool {
+            STATE.with(|state| state.borrow().moderators.contains(&account))
+        }
+
+        /// members and the moderators of the org count as members
+        pub fn is_member(org_address: AccountId, account: AccountId) -> bool {
+            STATE.with(|state| {
+                let state = state.borrow();
+                state.members.contains(&(org_address, account)) || state.moderators.contains(&account)
+            })
+        }
+
+        /// moderators count once, like `OrgManager::get_dao_member_count`
+        pub fn member_count(org_address: AccountId) -> u64 {
+            STATE.with(|state| {
+                let state = state.borrow();
+                let members = state.members.iter().filter(|(org, _)| *org == org_address).count();
+                let moderators_not_member = state.moderators.iter()
+                    .filter(|moderator| !state.members.contains(&(org_address, **moderator)))
+                    .count();
+                (members + moderators_not_member) as u64
+            })
+        }
+
+        pub fn has_permission(auth_address: AccountId, account: AccountId, contract_name: String, function_name: String) -> bool {
+            STATE.with(|state| state.borrow().permissions.contains(&(auth_address, account, contract_name, function_name)))
+        }
+
+        pub fn set_action_returns(returns: bool) {
+            STATE.with(|state| state.borrow_mut().action_returns = returns);
+        }
+
+        pub fn dispatched() -> Vec<(AccountId, VoteAction)> {
+            STATE.with(|state| state.borrow().dispatched.clone())
+        }
+
+        pub fn dispatch(caller: AccountId, action: &VoteAction) -> bool {
+            STATE.with(|state| {
+                let mut state = state.borrow_mut();
+                state.dispatched.push((caller, action.clone()));
+                !action.returns_bool || state.action_returns
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ink_lang as ink;
+
+        use super::*;
+        use ink_env::{
+            call,
+            test,
+        };
+
+        const DEFAULT_CALLEE_HASH: [u8; 32] = [0x07; 32];
+        const DEFAULT_ENDOWMENT: Balance = 1_000_000;
+        const DEFAULT_GAS_LIMIT: Balance = 1_000_000;
+
+        fn threshold(support_require_num: u64, min_approval_bps: u32) -> Threshold {
+            Threshold {
+                support_require_num,
+                min_require_num: 0,
+                min_participation_bps: 0,
+                min_approval_bps,
+            }
+        }
+
+        fn advance_time(blocks: u32) {
+            for _ in 0..blocks {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>().expect("Cannot advance block");
+            }
+        }
+
+        const ORG_HASH: [u8; 32] = [0x08; 32];
+        const TOKEN_HASH: [u8; 32] = [0x0A; 32];
+
+        /// votes dispatching a call need 10% participation and 50% approval, and are restricted
+        /// to the org members or weighted by the token
+        fn action_rules() -> ActionRules {
+            ActionRules {
+                min_participation_bps: 1000,
+                min_approval_bps: 5000,
+                token: Some(AccountId::from(TOKEN_HASH)),
+            }
+        }
+
+        /// the lowest threshold the action rules allow
+        fn action_threshold() -> Threshold {
+            let mut action_threshold = threshold(0, 5000);
+            action_threshold.min_participation_bps = 1000;
+            action_threshold
+        }
+
+        /// a vote manager whose guardian is django, the org is not deployed off-chain
+        fn new_vote_manager_with_guardian() -> VoteManager {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            VoteManager::new(AccountId::from(ORG_HASH), Some(accounts.django), action_rules())
+        }
+
+        fn action(callee: AccountId, selector: [u8; 4], returns_bool: bool) -> VoteAction {
+            VoteAction {
+                callee,
+                selector,
+                input: Vec::new(),
+                transferred_value: 0,
+                gas_limit: 0,
+                returns_bool,
+            }
+        }
+
+        fn index_ids(vote_manager: &VoteManager, index: VoteIndex) -> Vec<VoteId> {
+            (0..vote_manager.index_length(&index)).map(|position| vote_manager.index_entry(&index, position)).collect()
+        }
+
+        fn set_next_caller(caller: AccountId) {
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                AccountId::from(DEFAULT_CALLEE_HASH),
+                DEFAULT_ENDOWMENT,
+                DEFAULT_GAS_LIMIT,
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])),
+            )
+        }
+
+        #[ink::test]
+        fn test_split() {
+
+            let choices = "A,B,C".to_string();
+            let split = choices.split(",");
+            ink_env::debug_println("hello");
+            for s in split {
+                ink_env::debug_println(&s);
+            }
+        }
+
+        #[ink::test]
+        fn test_split_with_vec() {
+            let choices = "A,B,C".to_string();
+            let vec: Vec<&str> = choices.split(",").collect();
+            let i:u32 = 1;
+            let length = i + vec.len() as u32;
+            assert!(length == 4);
+            for s in vec{
+                ink_env::debug_println(&s);
+            }
+        }
+
+        #[ink::test]
+        fn test_calculate() {
+            let choice: u64 = 3;
+            let support: u64 = 5;
+            let t : u64 = choice * 1000 / support; 
+            ink_env::debug_println(t.to_string().as_str());
+        }
+
+        #[ink::test]
+        fn new_vote_manager() {
+            // let accounts =
             //     ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
             //         .expect("Cannot get accounts");
-            let vote_manager = VoteManager::new();
+            let vote_manager = new_vote_manager_with_guardian();
+
+            assert_eq!(vote_manager.votes_length, 0);
+            assert_eq!(vote_manager.query_guardian(), Some(AccountId::from([0x04; 32])));
+        }
+
+        #[ink::test]
+        fn unweighted_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(1, 0), vec!["A".to_string(), "B".to_string(), "C".to_string()], TallyMethod::Plurality, None, None, None, None).unwrap();
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            assert_eq!(vote_manager.vote(vote_id, 3), Err(Error::InvalidChoice));
+            assert_eq!(vote_manager.vote(vote_id + 1, 0), Err(Error::VoteNotExist));
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            assert_eq!(vote.support_num, 2);
+            let yeas: Vec<u64> = vote.choices.iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![1, 1, 0]);
+            assert_eq!(vote_manager.query_vote_choices(vote_id).unwrap()[1].content, "B");
+            assert_eq!(vote.erc20_address, None);
+        }
+
+        #[ink::test]
+        fn approval_threshold_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 7000), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            assert_eq!(vote.approval_bps, 6666);
+            assert_eq!(vote.participation_bps, 0);
+            advance_time(2);
+            assert_eq!(vote_manager.execute(vote_id), Err(Error::InvalidState));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().state, VoteState::Defeated);
+        }
 
+        #[ink::test]
+        fn invalid_threshold_fails() {
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 0, None, threshold(0, 10001), choices.clone(), TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::InvalidThreshold));
+            let mut participation = threshold(0, 0);
+            participation.min_participation_bps = 5000;
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 0, None, participation, choices.clone(), TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::UnknownElectorate));
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 0, None, threshold(0, 0), choices.clone(), TallyMethod::Quadratic, None, None, None, None);
+            assert_eq!(r, Err(Error::TokenRequired));
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), u64::MAX, 1, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::TimeOverflow));
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 1, u64::MAX, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::TimeOverflow));
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, Some(u64::MAX - 9), threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::TimeOverflow));
             assert_eq!(vote_manager.votes_length, 0);
         }
 
+        #[ink::test]
+        fn approval_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::Approval, None, None, None, None).unwrap();
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::BallotMismatch));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Approval(vec![0, 0])), Err(Error::InvalidBallot));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Approval(vec![0, 1])), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Approval(vec![1, 2])), Ok(()));
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            let yeas: Vec<u64> = vote.choices.iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![1, 2, 1]);
+            assert_eq!(vote.support_num, 2);
+            assert_eq!(vote.winner, Some(1));
+            assert_eq!(vote.approval_bps, 10000);
+        }
+
+        #[ink::test]
+        fn ranked_choice_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::RankedChoice, None, None, None, None).unwrap();
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::BallotMismatch));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![0, 0])), Err(Error::InvalidBallot));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![0, 1])), Ok(()));
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![0])), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![1])), Ok(()));
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![1, 0])), Ok(()));
+            set_next_caller(accounts.charlie);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Ranked(vec![2, 1])), Ok(()));
+
+            // the choices count first preferences, C is eliminated and its ballot goes to B
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            let yeas: Vec<u64> = vote.choices.iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![2, 2, 1]);
+            assert_eq!(vote.winner, Some(1));
+            assert_eq!(vote.approval_bps, 6000);
+        }
+
+        #[ink::test]
+        fn quadratic_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let vote_module = AccountId::from(DEFAULT_CALLEE_HASH);
+            let token_address = AccountId::from([0x0A; 32]);
+            let vault_address = AccountId::from([0x0B; 32]);
+            mock::set_balance(token_address, accounts.alice, 100);
+            mock::set_balance(token_address, accounts.bob, 100);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let other_token = AccountId::from([0x0C; 32]);
+            mock::deny_snapshots(other_token);
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices.clone(), TallyMethod::Quadratic, Some(other_token), None, None, None);
+            assert_eq!(r, Err(Error::SnapshotFailed));
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::Quadratic, Some(token_address), None, None, None).unwrap();
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().electorate, 200);
+
+            set_next_caller(accounts.alice);
+            mock::approve(token_address, accounts.alice, vote_module, 100);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 0, votes: 0 }), Err(Error::InvalidBallot));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 0, votes: 3 }), Err(Error::VaultNotSet));
+            mock::set_vault_address(vault_address);
+            // 3 votes cost 9 tokens, 2 more cost 25 - 9, paid to the vault
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 0, votes: 3 }), Ok(()));
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 0, votes: 2 }), Ok(()));
+            assert_eq!(mock::balance_of(token_address, accounts.alice), 75);
+            assert_eq!(mock::balance_of(token_address, vault_address), 25);
+            assert_eq!(mock::balance_of(token_address, vote_module), 0);
+
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 1, votes: 2 }), Err(Error::PaymentFailed));
+            mock::approve(token_address, accounts.bob, vote_module, 4);
+            assert_eq!(vote_manager.cast_ballot(vote_id, Ballot::Quadratic { choice_id: 1, votes: 2 }), Ok(()));
+            assert_eq!(mock::balance_of(token_address, vault_address), 29);
+
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            let yeas: Vec<u64> = vote.choices.iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![5, 2]);
+            assert_eq!((vote.support_num, vote.winner), (7, Some(0)));
+        }
+
+        #[ink::test]
+        fn quadratic_payments_are_final() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let vote_module = AccountId::from(DEFAULT_CALLEE_HASH);
+            let token_address = AccountId::from(TOKEN_HASH);
+            let vault_address = AccountId::from([0x0B; 32]);
+            mock::set_vault_address(vault_address);
+            mock::set_balance(token_address, accounts.alice, 100);
+            mock::approve(token_address, accounts.alice, vote_module, 100);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            set_next_caller(accounts.alice);
+            let cancelled = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices.clone(), TallyMethod::Quadratic, Some(token_address), None, None, None).unwrap();
+            let vetoed = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::Quadratic, Some(token_address), None, None, None).unwrap();
+            assert_eq!(vote_manager.cast_ballot(cancelled, Ballot::Quadratic { choice_id: 0, votes: 3 }), Ok(()));
+            assert_eq!(vote_manager.cast_ballot(vetoed, Ballot::Quadratic { choice_id: 1, votes: 4 }), Ok(()));
+            assert_eq!(mock::balance_of(token_address, vault_address), 25);
+
+            // the vault keeps the payments of cancelled and vetoed votes
+            assert_eq!(vote_manager.cancel(cancelled), Ok(()));
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.veto(vetoed, "harmful".to_string()), Ok(()));
+            assert_eq!(mock::balance_of(token_address, vault_address), 25);
+            assert_eq!(mock::balance_of(token_address, accounts.alice), 75);
+        }
+
+        #[ink::test]
+        fn delegation_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, Some("treasury".to_string())).unwrap();
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.delegate(accounts.alice, None), Err(Error::SelfDelegation));
+            assert_eq!(vote_manager.delegate(accounts.bob, None), Ok(()));
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.delegate(accounts.alice, Some("treasury".to_string())), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.delegate(accounts.eve, None), Err(Error::DelegationCycle));
+            assert_eq!(vote_manager.query_delegators(accounts.bob, None), vec![accounts.alice]);
+
+            // bob carries alice's vote, and through alice eve's
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().support_num, 3);
+
+            // voting directly overrides the delegation, eve still follows alice
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            let yeas: Vec<u64> = vote_manager.query_vote_choices(vote_id).unwrap().iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![1, 2]);
+
+            assert_eq!(vote_manager.undelegate(None), Ok(()));
+            assert_eq!(vote_manager.undelegate(None), Err(Error::DelegationNotExist));
+            assert_eq!(vote_manager.query_delegation(accounts.alice, None), None);
+        }
+
+        #[ink::test]
+        fn delegation_during_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            let yeas = |vote_manager: &VoteManager| -> Vec<u64> {
+                vote_manager.query_vote_choices(vote_id).unwrap().iter().map(|choice| choice.yea).collect()
+            };
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+
+            // delegating after the delegate voted moves the weight onto its ballot
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.delegate(accounts.bob, None), Ok(()));
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.delegate(accounts.alice, None), Ok(()));
+            assert_eq!(yeas(&vote_manager), vec![3, 0]);
+
+            // undelegating takes it back, alice and eve can't vote twice
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.undelegate(None), Ok(()));
+            assert_eq!(yeas(&vote_manager), vec![1, 0]);
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            assert_eq!(yeas(&vote_manager), vec![1, 2]);
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().support_num, 3);
+
+            // accounts which voted keep their weight
+            assert_eq!(vote_manager.delegate(accounts.bob, None), Ok(()));
+            assert_eq!(yeas(&vote_manager), vec![1, 2]);
+
+            // delegations made after the vote is over don't count
+            advance_time(2);
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.undelegate(None), Ok(()));
+            assert_eq!(yeas(&vote_manager), vec![1, 2]);
+        }
+
+        #[ink::test]
+        fn delegation_limits_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 100, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+
+            // weight passes through at most two delegations, frank's doesn't reach bob
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.delegate(accounts.bob, None), Ok(()));
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.delegate(accounts.alice, None), Ok(()));
+            set_next_caller(accounts.frank);
+            assert_eq!(vote_manager.delegate(accounts.eve, None), Ok(()));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().support_num, 3);
+            // until a closer delegate votes
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 1), Ok(()));
+            let yeas: Vec<u64> = vote_manager.query_vote_choices(vote_id).unwrap().iter().map(|choice| choice.yea).collect();
+            assert_eq!(yeas, vec![1, 3]);
+
+            // a delegate has at most ten delegations, for any tag
+            set_next_caller(accounts.charlie);
+            for index in 0..3 {
+                assert_eq!(vote_manager.delegate(accounts.django, Some(index.to_string())), Ok(()));
+            }
+            for index in 0..7u8 {
+                set_next_caller(AccountId::from([0x20 + index; 32]));
+                assert_eq!(vote_manager.delegate(accounts.django, None), Ok(()));
+            }
+            set_next_caller(AccountId::from([0x30; 32]));
+            assert_eq!(vote_manager.delegate(accounts.django, None), Err(Error::TooManyDelegators));
+            // delegating again to the same delegate doesn't count twice
+            set_next_caller(AccountId::from([0x20; 32]));
+            assert_eq!(vote_manager.delegate(accounts.django, None), Ok(()));
+            assert_eq!(vote_manager.undelegate(None), Ok(()));
+            set_next_caller(AccountId::from([0x30; 32]));
+            assert_eq!(vote_manager.delegate(accounts.django, None), Ok(()));
+
+            // an account delegates for at most five tags
+            set_next_caller(accounts.charlie);
+            for index in 3..5 {
+                assert_eq!(vote_manager.delegate(accounts.eve, Some(index.to_string())), Ok(()));
+            }
+            assert_eq!(vote_manager.delegate(accounts.eve, Some("5".to_string())), Err(Error::TooManyDelegationTags));
+            assert_eq!(vote_manager.delegate(accounts.eve, Some("0".to_string())), Ok(()));
+            assert_eq!(vote_manager.delegate(accounts.eve, None), Ok(()));
+        }
+
+        #[ink::test]
+        fn secret_ballot_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, Some(0), threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None);
+            assert_eq!(r, Err(Error::InvalidRevealTime));
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, Some(10), threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            let salt = Hash::from([0x01; 32]);
+            let commitment = vote_manager.compute_commitment(Ballot::Single(1), salt);
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 1), Err(Error::WrongPhase));
+            assert_eq!(vote_manager.commit_ballot(vote_id, commitment), Ok(()));
+            assert_eq!(vote_manager.reveal_ballot(vote_id, Ballot::Single(1), salt), Err(Error::WrongPhase));
+            assert_eq!(vote_manager.query_open_vote(None, 10).votes[0].phase, VotePhase::Commit);
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().support_num, 0);
+
+            advance_time(2);
+            assert_eq!(vote_manager.query_open_vote(None, 10).votes[0].phase, VotePhase::Reveal);
+            assert_eq!(vote_manager.reveal_ballot(vote_id, Ballot::Single(0), salt), Err(Error::CommitmentMismatch));
+            assert_eq!(vote_manager.reveal_ballot(vote_id, Ballot::Single(1), salt), Ok(()));
+            assert_eq!(vote_manager.reveal_ballot(vote_id, Ballot::Single(1), salt), Err(Error::CommitmentNotExist));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().winner, Some(1));
+
+            advance_time(2);
+            assert_eq!(vote_manager.query_open_vote(None, 10).votes.len(), 0);
+            assert_eq!(vote_manager.query_wait_vote(None, 10).votes[0].phase, VotePhase::Closed);
+        }
+
+        #[ink::test]
+        fn vote_lifecycle_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            set_next_caller(accounts.alice);
+            let vetoed = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 10, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            let cancelled = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 10, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            let executed = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 10, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(vote_manager.query_one_vote(vetoed).unwrap().state, VoteState::Pending);
+            assert_eq!(vote_manager.vote(vetoed, 0), Err(Error::InvalidState));
+
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.cancel(cancelled), Err(Error::NotProposer));
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.cancel(cancelled), Ok(()));
+            assert_eq!(vote_manager.cancel(cancelled), Err(Error::InvalidState));
+            assert_eq!(vote_manager.query_one_vote(cancelled).unwrap().state, VoteState::Cancelled);
+
+            advance_time(2);
+            assert_eq!(vote_manager.query_one_vote(vetoed).unwrap().state, VoteState::Active);
+            assert_eq!(vote_manager.vote(vetoed, 0), Ok(()));
+            assert_eq!(vote_manager.vote(executed, 1), Ok(()));
+            assert_eq!(vote_manager.execute(executed), Err(Error::InvalidState));
+
+            // a vote exactly at its deadline is over
+            advance_time(2);
+            assert_eq!(vote_manager.query_one_vote(vetoed).unwrap().state, VoteState::Succeeded);
+            assert_eq!(vote_manager.query_wait_vote(None, 10).votes.len(), 2);
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.veto(vetoed, "harmful".to_string()), Ok(()));
+            assert_eq!(vote_manager.execute(vetoed), Err(Error::InvalidState));
+            let vote = vote_manager.query_one_vote(vetoed).unwrap();
+            assert_eq!(vote.state, VoteState::Vetoed);
+            assert_eq!(vote.veto_reason, Some("harmful".to_string()));
+
+            assert_eq!(vote_manager.execute(executed), Ok(()));
+            assert_eq!(vote_manager.veto(executed, "too late".to_string()), Err(Error::InvalidState));
+            assert_eq!(vote_manager.query_executed_vote(None, 10).votes[0].vote_id, executed);
+        }
+
+        #[ink::test]
+        fn veto_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let mut vote_ids = Vec::new();
+            for _ in 0..2 {
+                vote_ids.push(vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap());
+            }
+
+            // neither the guardian nor a moderator
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.veto(vote_ids[0], "harmful".to_string()), Err(Error::NotGuardian));
+            assert_eq!(vote_manager.query_one_vote(vote_ids[0]).unwrap().state, VoteState::Active);
+            mock::add_moderator(accounts.bob);
+            assert_eq!(vote_manager.veto(vote_ids[0], "harmful".to_string()), Ok(()));
+
+            // moderators may veto but not replace the guardian
+            assert_eq!(vote_manager.set_guardian(Some(accounts.bob)), Err(Error::NotGuardian));
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.set_guardian(Some(accounts.eve)), Ok(()));
+            assert_eq!(vote_manager.query_guardian(), Some(accounts.eve));
+            assert_eq!(vote_manager.veto(vote_ids[1], "harmful".to_string()), Err(Error::NotGuardian));
+            assert_eq!(vote_manager.set_guardian(None), Err(Error::NotGuardian));
+
+            // the vote module itself, through an executed vote
+            set_next_caller(AccountId::from(DEFAULT_CALLEE_HASH));
+            assert_eq!(vote_manager.set_guardian(None), Ok(()));
+            assert_eq!(vote_manager.query_guardian(), None);
+        }
+
+        #[ink::test]
+        fn set_guardian_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let vote_module = AccountId::from(DEFAULT_CALLEE_HASH);
+            mock::add_member(AccountId::from(ORG_HASH), accounts.alice);
+            mock::add_member(AccountId::from(ORG_HASH), accounts.bob);
+            // selector of `VoteManager::set_guardian`, replacing the guardian by none
+            let mut set_guardian = action(vote_module, [0x51, 0x33, 0xED, 0x53], false);
+            set_guardian.input = scale::Encode::encode(&Option::<AccountId>::None);
+            let choices = vec!["A".to_string(), "B".to_string()];
+
+            // open to anyone with no threshold, as any vote with an action
+            set_next_caller(accounts.alice);
+            let r = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, Some(set_guardian.clone()), None, None);
+            assert_eq!(r, Err(Error::ActionRulesNotMet));
+            let org_members = Some(Eligibility::OrgMember(AccountId::from(ORG_HASH)));
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, action_threshold(), choices, TallyMethod::Plurality, None, Some(set_guardian.clone()), org_members, None).unwrap();
+            // outsiders can't vote for it
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::NotEligible));
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            advance_time(2);
+            assert_eq!(vote_manager.execute(vote_id), Ok(()));
+            assert_eq!(mock::dispatched(), vec![(vote_module, set_guardian)]);
+        }
+
+        #[ink::test]
+        fn vote_action_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            mock::add_member(AccountId::from(ORG_HASH), accounts.alice);
+            mock::set_balance(AccountId::from(TOKEN_HASH), accounts.alice, 100);
+            let callee = AccountId::from([0x09; 32]);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let mut vote_ids = Vec::new();
+            for returns_bool in &[true, true, false] {
+                let vote_action = action(callee, [0x01, 0x02, 0x03, 0x04], *returns_bool);
+                set_next_caller(accounts.alice);
+                let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, action_threshold(), choices.clone(), TallyMethod::Plurality, Some(AccountId::from(TOKEN_HASH)), Some(vote_action), None, None).unwrap();
+                set_next_caller(accounts.alice);
+                assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+                vote_ids.push(vote_id);
+            }
+            advance_time(2);
+
+            // the callee returning false fails the action
+            mock::set_action_returns(false);
+            assert_eq!(vote_manager.execute(vote_ids[0]), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_ids[0]).unwrap().action_result, Some(false));
+            // nothing is decoded from functions which don't return a bool
+            assert_eq!(vote_manager.execute(vote_ids[2]), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_ids[2]).unwrap().action_result, Some(true));
+            mock::set_action_returns(true);
+            assert_eq!(vote_manager.execute(vote_ids[1]), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_ids[1]).unwrap().action_result, Some(true));
+
+            // the callee sees the vote module as caller
+            let dispatched = mock::dispatched();
+            assert_eq!(dispatched.len(), 3);
+            assert!(dispatched.iter().all(|(caller, vote_action)| *caller == AccountId::from(DEFAULT_CALLEE_HASH) && vote_action.callee == callee));
+        }
+
+        #[ink::test]
+        fn action_rules_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let token_address = AccountId::from(TOKEN_HASH);
+            mock::add_member(AccountId::from(ORG_HASH), accounts.alice);
+            mock::set_balance(token_address, accounts.alice, 100);
+            let vote_action = action(AccountId::from([0x09; 32]), [0x01, 0x02, 0x03, 0x04], true);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let new_action_vote = |vote_manager: &mut VoteManager, threshold: Threshold, erc20_address: Option<AccountId>, eligibility: Option<Eligibility>| {
+                vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold, choices.clone(), TallyMethod::Plurality, erc20_address, Some(vote_action.clone()), eligibility, None)
+            };
+
+            // only members and moderators of the org propose calls
+            set_next_caller(accounts.bob);
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), Some(token_address), None), Err(Error::NotEligible));
+            // open to anyone, restricted to another org or weighted by another token
+            set_next_caller(accounts.alice);
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), None, None), Err(Error::ActionRulesNotMet));
+            let other_org = Some(Eligibility::OrgMember(AccountId::from([0x0D; 32])));
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), None, other_org), Err(Error::ActionRulesNotMet));
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), Some(AccountId::from([0x0C; 32])), None), Err(Error::ActionRulesNotMet));
+            // the proposer can't lower the minimums
+            assert_eq!(new_action_vote(&mut vote_manager, threshold(0, 5000), Some(token_address), None), Err(Error::ActionRulesNotMet));
+            let mut low_approval = action_threshold();
+            low_approval.min_approval_bps = 4999;
+            assert_eq!(new_action_vote(&mut vote_manager, low_approval, Some(token_address), None), Err(Error::ActionRulesNotMet));
+            assert_eq!(vote_manager.votes_length, 0);
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), Some(token_address), None), Ok(0));
+            mock::add_moderator(accounts.bob);
+            set_next_caller(accounts.bob);
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), Some(token_address), None), Ok(1));
+            let own_org = Some(Eligibility::OrgMember(AccountId::from(ORG_HASH)));
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), None, own_org), Ok(2));
+
+            // the rules are only changed by the vote module itself
+            let mut rules = action_rules();
+            rules.min_approval_bps = 6000;
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.set_action_rules(rules), Err(Error::NotVoteModule));
+            set_next_caller(AccountId::from(DEFAULT_CALLEE_HASH));
+            rules.min_participation_bps = 10001;
+            assert_eq!(vote_manager.set_action_rules(rules), Err(Error::InvalidThreshold));
+            rules.min_participation_bps = 1000;
+            assert_eq!(vote_manager.set_action_rules(rules), Ok(()));
+            assert_eq!(vote_manager.query_action_rules(), rules);
+            set_next_caller(accounts.alice);
+            assert_eq!(new_action_vote(&mut vote_manager, action_threshold(), Some(token_address), None), Err(Error::ActionRulesNotMet));
+        }
+
+        #[ink::test]
+        fn org_member_eligibility_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let org_address = AccountId::from(ORG_HASH);
+            mock::add_member(org_address, accounts.alice);
+            mock::add_member(org_address, accounts.bob);
+            mock::add_moderator(accounts.charlie);
+            mock::add_member(AccountId::from([0x0D; 32]), accounts.eve);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let mut participation = threshold(0, 0);
+            participation.min_participation_bps = 5000;
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, participation, choices, TallyMethod::Plurality, None, None, Some(Eligibility::OrgMember(org_address)), None).unwrap();
+            // the members and the moderator are the electorate
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().electorate, 3);
+
+            // members of another org and outsiders are rejected
+            set_next_caller(accounts.eve);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::NotEligible));
+            set_next_caller(accounts.django);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::NotEligible));
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            set_next_caller(accounts.charlie);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            let vote = vote_manager.query_one_vote(vote_id).unwrap();
+            assert_eq!((vote.support_num, vote.participation_bps), (2, 6666));
+            advance_time(2);
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().state, VoteState::Succeeded);
+        }
+
+        #[ink::test]
+        fn auth_eligibility_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            let auth_address = AccountId::from([0x0E; 32]);
+            mock::grant_permission(auth_address, accounts.alice, "vault", "withdraw");
+            mock::grant_permission(auth_address, accounts.bob, "vault", "deposit");
+            let eligibility = Eligibility::AuthAction {
+                auth_address,
+                contract_name: "vault".to_string(),
+                function_name: "withdraw".to_string(),
+            };
+            let choices = vec!["A".to_string(), "B".to_string()];
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, Some(eligibility), None).unwrap();
+
+            // another permission of the auth module doesn't count
+            set_next_caller(accounts.bob);
+            assert_eq!(vote_manager.vote(vote_id, 0), Err(Error::NotEligible));
+            set_next_caller(accounts.alice);
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().support_num, 1);
+        }
+
+        #[ink::test]
+        fn accept_ownership_vote_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            mock::reset();
+            mock::add_member(AccountId::from(ORG_HASH), accounts.alice);
+            mock::set_balance(AccountId::from(TOKEN_HASH), accounts.alice, 100);
+            // selector of `OrgManager::accept_ownership`
+            let accept_ownership = action(AccountId::from(ORG_HASH), [0xB5, 0x5B, 0xE9, 0xF0], true);
+            let choices = vec!["A".to_string(), "B".to_string()];
+            set_next_caller(accounts.alice);
+            let vote_id = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, action_threshold(), choices, TallyMethod::Plurality, Some(AccountId::from(TOKEN_HASH)), Some(accept_ownership.clone()), None, None).unwrap();
+            assert_eq!(vote_manager.vote(vote_id, 0), Ok(()));
+            advance_time(2);
+            assert_eq!(vote_manager.execute(vote_id), Ok(()));
+            assert_eq!(vote_manager.query_one_vote(vote_id).unwrap().action_result, Some(true));
+            // the org sees the vote module as the caller accepting the ownership
+            assert_eq!(mock::dispatched(), vec![(AccountId::from(DEFAULT_CALLEE_HASH), accept_ownership)]);
+        }
+
+        #[ink::test]
+        fn vote_queries_work() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            set_next_caller(accounts.alice);
+            for start_delay in &[0, 10] {
+                vote_manager.new_vote("hello".to_string(), "hello world".to_string(), *start_delay, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            }
+            set_next_caller(accounts.bob);
+            vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            set_next_caller(accounts.alice);
+            let cancelled = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(vote_manager.cancel(cancelled), Ok(()));
+
+            let ids = |page: &VotePage| page.votes.iter().map(|vote| vote.vote_id).collect::<Vec<VoteId>>();
+            let page = vote_manager.query_votes(VoteFilter::default(), None, 2);
+            assert_eq!((ids(&page), page.next_cursor), (vec![0, 1], Some(2)));
+            let page = vote_manager.query_votes(VoteFilter::default(), page.next_cursor, 2);
+            assert_eq!((ids(&page), page.next_cursor), (vec![2, 3], None));
+
+            let by_alice = VoteFilter { proposer: Some(accounts.alice), ..Default::default() };
+            assert_eq!(ids(&vote_manager.query_votes(by_alice, Some(1), 10)), vec![1, 3]);
+            let pending = VoteFilter { state: Some(VoteState::Pending), ..Default::default() };
+            assert_eq!(ids(&vote_manager.query_votes(pending, None, 10)), vec![1]);
+            let cancelled_votes = VoteFilter { state: Some(VoteState::Cancelled), ..Default::default() };
+            assert_eq!(ids(&vote_manager.query_votes(cancelled_votes, None, 10)), vec![cancelled]);
+            let later = VoteFilter { start_from: Some(5), start_to: Some(20), ..Default::default() };
+            assert_eq!(ids(&vote_manager.query_votes(later, None, 10)), vec![1]);
+
+            let count = vote_manager.vote_count_by_state();
+            assert_eq!((count.pending, count.active, count.cancelled, count.defeated), (1, 2, 1, 0));
+            advance_time(2);
+            let count = vote_manager.vote_count_by_state();
+            assert_eq!((count.pending, count.active, count.defeated), (0, 1, 2));
+        }
+
+        #[ink::test]
+        fn finished_votes_settle_works() {
+            let accounts =
+                ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot get accounts");
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            set_next_caller(accounts.alice);
+            let defeated = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            let succeeded = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(vote_manager.vote(succeeded, 0), Ok(()));
+            advance_time(2);
+
+            // the votes over are swept from the live ones by the next vote
+            let open = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Live), vec![open]);
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Succeeded), vec![succeeded]);
+            assert_eq!(vote_manager.query_one_vote(defeated).unwrap().state, VoteState::Defeated);
+            let count = vote_manager.vote_count_by_state();
+            assert_eq!((count.active, count.succeeded, count.defeated), (1, 1, 1));
+
+            let ids = |page: &VotePage| page.votes.iter().map(|vote| vote.vote_id).collect::<Vec<VoteId>>();
+            let page = vote_manager.query_wait_vote(None, 1);
+            assert_eq!((ids(&page), page.next_cursor), (vec![defeated], Some(1)));
+            let page = vote_manager.query_wait_vote(page.next_cursor, 1);
+            assert_eq!(ids(&page), vec![succeeded]);
+            let page = vote_manager.query_open_vote(None, 10);
+            assert_eq!((ids(&page), page.next_cursor), (vec![open], None));
+
+            // succeeded votes leave their index once executed
+            assert_eq!(vote_manager.execute(succeeded), Ok(()));
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Succeeded), Vec::<VoteId>::new());
+            assert_eq!(vote_manager.query_wait_vote(None, 10).votes.len(), 1);
+            assert_eq!(vote_manager.query_executed_vote(None, 10).votes[0].vote_id, succeeded);
+        }
+
+        #[ink::test]
+        fn sweep_is_bounded_works() {
+            let mut vote_manager = new_vote_manager_with_guardian();
+            let choices = vec!["A".to_string(), "B".to_string()];
+            for _ in 0..25 {
+                vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            }
+            advance_time(2);
+
+            // a sweep settles at most MAX_SWEEP votes, the others are still counted as defeated
+            let open = vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices.clone(), TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Settled(VoteState::Defeated)).len(), 20);
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Live).len(), 6);
+            let count = vote_manager.vote_count_by_state();
+            assert_eq!((count.active, count.defeated), (1, 25));
+            assert_eq!(vote_manager.query_votes(VoteFilter { state: Some(VoteState::Defeated), ..Default::default() }, None, 50).votes.len(), 25);
+            vote_manager.new_vote("hello".to_string(), "hello world".to_string(), 0, 10, None, threshold(0, 0), choices, TallyMethod::Plurality, None, None, None, None).unwrap();
+            assert_eq!(index_ids(&vote_manager, VoteIndex::Live).len(), 2);
+            assert!(index_ids(&vote_manager, VoteIndex::Live).contains(&open));
+
+            // every vote, paged by id
+            let page = vote_manager.query_all_vote(None, 20);
+            assert_eq!((page.votes.len(), page.next_cursor), (20, Some(20)));
+            let page = vote_manager.query_all_vote(page.next_cursor, 20);
+            assert_eq!((page.votes.len(), page.next_cursor), (7, None));
+            assert_eq!(page.votes[5].vote_id, open);
+        }
+
+        #[test]
+        fn instant_runoff_works() {
+            // first preferences A:4 B:3 C:2, C is eliminated and its votes go to B
+            let a_first: &[ChoiceId] = &[0, 1];
+            let b_first: &[ChoiceId] = &[1];
+            let c_first: &[ChoiceId] = &[2, 1];
+            let rankings = [(a_first, 4), (b_first, 3), (c_first, 2)];
+            assert_eq!(instant_runoff(3, &rankings), Some((1, 5)));
+            let tied = [(a_first, 2), (b_first, 2)];
+            assert_eq!(instant_runoff(3, &tied), None);
+            assert_eq!(plurality_winner(&[1, 3, 2]), Some((1, 3)));
+            assert_eq!(plurality_winner(&[3, 3, 2]), None);
+        }
+
         // #[ink::test]
         // fn new_vote() {
         //     let accounts =